@@ -0,0 +1,132 @@
+use toml::{Table, Value};
+
+/// Applies the defaults to the options of a feed.
+///
+/// Tables are merged recursively, other values of the feed replace the
+/// default. A feed with its own `sink` or `sinks` does not inherit the
+/// other one, and a sink of a different type replaces the default sink.
+pub fn apply(defaults: &Table, mut feed: Table) -> Table {
+    let has_sinks = feed.contains_key("sink") || feed.contains_key("sinks");
+
+    for (key, default) in defaults {
+        match (key.as_str(), feed.get_mut(key)) {
+            ("sink", Some(sink)) => {
+                if sink_type(sink).map_or(true, |v| Some(v) == sink_type(default)) {
+                    merge(sink, default);
+                }
+            }
+            ("sink" | "sinks", None) if has_sinks => {}
+            (_, Some(value)) => merge(value, default),
+            (_, None) => {
+                feed.insert(key.clone(), default.clone());
+            }
+        }
+    }
+
+    feed
+}
+
+/// Merges the default into the value, keeping the value where both differ
+fn merge(value: &mut Value, default: &Value) {
+    let (Value::Table(value), Value::Table(default)) = (value, default) else {
+        return;
+    };
+
+    for (key, default) in default {
+        match value.get_mut(key) {
+            Some(value) => merge(value, default),
+            None => {
+                value.insert(key.clone(), default.clone());
+            }
+        }
+    }
+}
+
+/// Returns the type of inline sink options, if set
+fn sink_type(sink: &Value) -> Option<&str> {
+    sink.as_table()?.get("type")?.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_defaults() {
+        let cases = [
+            // Missing options are inherited
+            (
+                "interval = \"5m\"",
+                "url = \"a\"",
+                "url = \"a\"\ninterval = \"5m\"",
+            ),
+            // Values of the feed win
+            (
+                "interval = \"5m\"",
+                "interval = \"1m\"",
+                "interval = \"1m\"",
+            ),
+            // Tables are merged recursively
+            (
+                "retry = { attempts = 3, backoff = { min = \"1s\", max = \"1m\" } }",
+                "retry = { backoff = { max = \"5m\" } }",
+                "retry = { attempts = 3, backoff = { min = \"1s\", max = \"5m\" } }",
+            ),
+            // Arrays are replaced, not merged
+            ("tags = [\"a\", \"b\"]", "tags = [\"c\"]", "tags = [\"c\"]"),
+            // A table in the feed replaces a scalar default and vice versa
+            ("a = 1", "a = { b = 2 }", "a = { b = 2 }"),
+            ("a = { b = 2 }", "a = 1", "a = 1"),
+            // Sinks of the same or no type are merged
+            (
+                "sink = { type = \"slack\", url = \"u\", quota = \"1/s\" }",
+                "sink = { type = \"slack\", quota = \"2/s\" }",
+                "sink = { type = \"slack\", url = \"u\", quota = \"2/s\" }",
+            ),
+            (
+                "sink = { type = \"slack\", url = \"u\" }",
+                "sink = { url = \"v\" }",
+                "sink = { type = \"slack\", url = \"v\" }",
+            ),
+            // A sink of a different type or a named sink replaces the default
+            (
+                "sink = { type = \"slack\", url = \"u\" }",
+                "sink = { type = \"discord\", url = \"v\" }",
+                "sink = { type = \"discord\", url = \"v\" }",
+            ),
+            (
+                "sink = { type = \"slack\", url = \"u\" }",
+                "sink = \"named\"",
+                "sink = \"named\"",
+            ),
+            // `sink` and `sinks` exclude each other
+            (
+                "sink = \"named\"",
+                "sinks = [\"other\"]",
+                "sinks = [\"other\"]",
+            ),
+            (
+                "sinks = [\"a\", \"b\"]",
+                "sink = \"other\"",
+                "sink = \"other\"",
+            ),
+            (
+                "sinks = [\"a\"]",
+                "url = \"a\"",
+                "url = \"a\"\nsinks = [\"a\"]",
+            ),
+        ];
+
+        for (defaults, feed, expected) in cases {
+            let defaults: Table = toml::from_str(defaults).unwrap();
+            let feed: Table = toml::from_str(feed).unwrap();
+            let expected: Table = toml::from_str(expected).unwrap();
+
+            assert_eq!(
+                apply(&defaults, feed.clone()),
+                expected,
+                "{defaults:?} {feed:?}"
+            );
+        }
+    }
+}
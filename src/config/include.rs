@@ -0,0 +1,72 @@
+use crate::{error::Error, Result};
+
+use std::path::{Path, PathBuf};
+
+use glob::Pattern;
+use tokio::fs;
+
+/// Returns the `*.toml` files of the directory, sorted by name
+pub async fn toml_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|v| v == "toml") && entry.file_type().await?.is_file() {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// Returns the files matching the include patterns of `file`, in the order
+/// of the patterns and sorted by name for each pattern.
+///
+/// Relative patterns are resolved against the directory of `file`. The
+/// directories that are searched are added to `dirs`, so that new files can
+/// be detected.
+pub fn expand(file: &Path, patterns: &[String], dirs: &mut Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let base = file.parent().unwrap_or(Path::new(""));
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        let full = match Path::new(pattern).is_absolute() {
+            true => pattern.clone(),
+            false => Path::new(&Pattern::escape(&base.to_string_lossy()))
+                .join(pattern)
+                .to_string_lossy()
+                .into_owned(),
+        };
+
+        let paths = glob::glob(&full).map_err(|e| {
+            Error::Config(format!(
+                "invalid include pattern \"{pattern}\" in \"{}\": {e}",
+                file.display()
+            ))
+        })?;
+
+        let mut matches = Vec::new();
+        for path in paths {
+            let path = path.map_err(|e| Error::Io(e.into()))?;
+            if path.is_file() {
+                matches.push(path);
+            }
+        }
+
+        // A path without wildcards must exist
+        if matches.is_empty() && !pattern.contains(['*', '?', '[']) {
+            matches.push(base.join(pattern));
+        }
+
+        if let Some(dir) = base.join(pattern).parent() {
+            dirs.push(dir.to_path_buf());
+        }
+
+        files.extend(matches);
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,714 @@
+mod defaults;
+mod include;
+mod substitute;
+
+use crate::{
+    error::Error,
+    filter::Filter,
+    retry::RetryPolicy,
+    sink::SinkOptions,
+    state::StoreOptions,
+    supervisor::FailurePolicy,
+    watcher::{Detection, Undated},
+    Result,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Url,
+};
+use serde::{
+    de::{self, DeserializeOwned, IntoDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use tokio::fs;
+use toml::{Spanned, Table};
+use toml_edit::{ImDocument, InlineTable, Item, TableLike, Value};
+use tracing::warn;
+
+#[derive(Debug, Default)]
+pub struct Config {
+    pub feeds: HashMap<String, Feed>,
+    /// Named sinks that can be shared between feeds
+    pub sinks: HashMap<String, SinkOptions>,
+    pub state: Option<StoreOptions>,
+    /// Files and directories the config was read from
+    pub sources: Vec<PathBuf>,
+}
+
+/// A single config file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    /// Glob patterns of further config files, relative to this file
+    #[serde(default)]
+    include: Vec<String>,
+    /// Options that apply to every feed, unless the feed overrides them
+    defaults: Option<Table>,
+    /// The feed options are deserialized after the defaults of all files
+    /// have been applied
+    #[serde(default)]
+    feeds: HashMap<String, Spanned<Table>>,
+    /// The sink and state options are deserialized separately, so that
+    /// errors point to the invalid option
+    #[serde(default)]
+    sinks: HashMap<String, Spanned<Table>>,
+    state: Option<Spanned<Table>>,
+}
+
+/// Feed options that have not been deserialized yet
+#[derive(Debug)]
+struct RawFeed {
+    options: Table,
+    source: Source,
+    location: Location,
+}
+
+/// A config file and its content after substitution
+#[derive(Debug, Clone)]
+struct Source {
+    file: PathBuf,
+    content: Arc<str>,
+}
+
+/// Position in a config file for error messages
+#[derive(Debug, Clone)]
+struct Location {
+    file: PathBuf,
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    fn new(file: &Path, content: &str, offset: usize) -> Self {
+        let (line, column) = line_column(content, offset);
+
+        Self {
+            file: file.to_path_buf(),
+            line,
+            column,
+        }
+    }
+
+    /// Adds the location and the name of the item to the error message
+    fn error(&self, item: &str, err: Error) -> Error {
+        let msg = match err {
+            Error::Config(msg) => msg,
+            err => err.to_string(),
+        };
+
+        Error::Config(format!(
+            "{}: {item} at line {}, column {}: {msg}",
+            self.file.display(),
+            self.line,
+            self.column
+        ))
+    }
+}
+
+/// Returns the line and column of the byte offset, starting at 1
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+impl Config {
+    /// Reads the config from a file or from all `*.toml` files of a
+    /// directory, together with the files they include
+    pub async fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config = Self::default();
+        let mut builder = Builder::default();
+
+        let files = if fs::metadata(path).await?.is_dir() {
+            config.sources.push(path.to_path_buf());
+            include::toml_files(path).await?
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        for file in files {
+            // Files of the directory might already be included by another one
+            if config.sources.contains(&file) {
+                continue;
+            }
+
+            let mut part = File::read(&file).await?;
+            let patterns = mem::take(&mut part.0.include);
+            builder.merge(&mut config, part, &file)?;
+
+            for path in include::expand(&file, &patterns, &mut config.sources)? {
+                // A file might match its own patterns
+                if config.sources.contains(&path) {
+                    continue;
+                }
+
+                let part = File::read(&path).await?;
+                if !part.0.include.is_empty() {
+                    return Err(Error::Config(format!(
+                        "included file \"{}\" must not include further files",
+                        path.display()
+                    )));
+                }
+                builder.merge(&mut config, part, &path)?;
+            }
+        }
+
+        config.feeds = builder.feeds()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the references between feeds and sinks. The feeds and sinks
+    /// themselves are validated when they are read.
+    fn validate(&self) -> Result<()> {
+        for (name, feed) in self.feeds.iter() {
+            for sink in feed.sinks() {
+                if let SinkRef::Named(sink) = sink {
+                    if !self.sinks.contains_key(sink) {
+                        return Err(Error::Config(format!(
+                            "feed \"{name}\" references unknown sink \"{sink}\""
+                        )));
+                    }
+                }
+            }
+        }
+
+        let referenced = self.referenced_sinks();
+        for name in self.sinks.keys() {
+            if !referenced.contains(name) {
+                warn!(sink = %name, "sink is not referenced by any feed");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the names of the named sinks that are referenced by feeds
+    pub fn referenced_sinks(&self) -> HashSet<String> {
+        self.feeds
+            .values()
+            .flat_map(|v| v.sinks())
+            .filter_map(|v| match v {
+                SinkRef::Named(name) => Some(name.clone()),
+                SinkRef::Inline(_) => None,
+            })
+            .collect()
+    }
+}
+
+impl File {
+    /// Reads the file and returns it together with its substituted content
+    async fn read(path: &Path) -> Result<(Self, String)> {
+        let read = async {
+            let raw = fs::read_to_string(path).await?;
+            let content = substitute::substitute(&raw)?;
+            Ok((toml::from_str(&content)?, content))
+        };
+
+        read.await.map_err(|e| match e {
+            Error::Config(msg) => Error::Config(format!("{}: {msg}", path.display())),
+            e => Error::Config(format!("{}: {e}", path.display())),
+        })
+    }
+}
+
+/// Collects the parts of the config from all files
+#[derive(Debug, Default)]
+struct Builder {
+    /// The file in which each feed, sink, etc. is defined
+    origins: HashMap<String, PathBuf>,
+    defaults: Table,
+    defaults_source: Option<Source>,
+    feeds: HashMap<String, RawFeed>,
+}
+
+impl Builder {
+    /// Adds the parts of a config file. Each of them must only be defined
+    /// once across all files.
+    fn merge(
+        &mut self,
+        config: &mut Config,
+        (part, content): (File, String),
+        file: &Path,
+    ) -> Result<()> {
+        let defined = part
+            .feeds
+            .keys()
+            .map(|v| format!("feed \"{v}\""))
+            .chain(part.sinks.keys().map(|v| format!("sink \"{v}\"")))
+            .chain(part.state.iter().map(|_| "state".to_string()))
+            .chain(part.defaults.iter().map(|_| "defaults".to_string()));
+
+        for name in defined {
+            if let Some(other) = self.origins.insert(name.clone(), file.to_path_buf()) {
+                return Err(Error::Config(format!(
+                    "{name} is defined in \"{}\" and \"{}\"",
+                    other.display(),
+                    file.display()
+                )));
+            }
+        }
+
+        let source = Source {
+            file: file.to_path_buf(),
+            content: content.into(),
+        };
+
+        for (name, options) in part.feeds {
+            let feed = RawFeed {
+                location: Location::new(file, &source.content, options.span().start),
+                options: options.into_inner(),
+                source: source.clone(),
+            };
+            self.feeds.insert(name, feed);
+        }
+
+        for (name, options) in part.sinks {
+            let item = format!("sink \"{name}\"");
+            let path = ["sinks", name.as_str()];
+            let location = Location::new(file, &source.content, options.span().start);
+
+            let options: SinkOptions = deserialize(options, &source, &path, &item)?;
+
+            options.validate().map_err(|(key, err)| {
+                locate_key(&source, &path, (key, None))
+                    .unwrap_or(location)
+                    .error(&item, *err)
+            })?;
+
+            config.sinks.insert(name, options);
+        }
+
+        if let Some(options) = part.state {
+            let options = deserialize(options, &source, &["state"], "state")?;
+            config.state = config.state.take().or(Some(options));
+        }
+
+        if let Some(defaults) = part.defaults {
+            self.defaults = defaults;
+            self.defaults_source = Some(source);
+        }
+        config.sources.push(file.to_path_buf());
+
+        Ok(())
+    }
+
+    /// Applies the defaults, deserializes and validates the feeds
+    fn feeds(self) -> Result<HashMap<String, Feed>> {
+        self.feeds
+            .into_iter()
+            .map(|(name, raw)| {
+                let item = format!("feed \"{name}\"");
+
+                let options = defaults::apply(&self.defaults, raw.options);
+                let feed = Feed::deserialize(toml::Value::Table(options)).map_err(|e| {
+                    let err = Error::Config(e.message().to_string());
+
+                    if let Some(location) =
+                        locate::<Feed>(&raw.source, &["feeds", &name], e.message())
+                    {
+                        return location.error(&item, err);
+                    }
+
+                    // An error in the defaults
+                    let defaults = self
+                        .defaults_source
+                        .as_ref()
+                        .and_then(|source| locate::<Feed>(source, &["defaults"], e.message()));
+                    match defaults {
+                        Some(location) => location.error("defaults", err),
+                        None => raw.location.error(&item, err),
+                    }
+                })?;
+
+                feed.validate().map_err(|(key, err)| {
+                    let err = *err;
+
+                    if let Some(location) = locate_key(&raw.source, &["feeds", &name], key) {
+                        return location.error(&item, err);
+                    }
+
+                    // An option of the defaults
+                    let defaults = self
+                        .defaults_source
+                        .as_ref()
+                        .and_then(|source| locate_key(source, &["defaults"], key));
+                    match defaults {
+                        Some(location) => location.error("defaults", err),
+                        None => raw.location.error(&item, err),
+                    }
+                })?;
+
+                Ok((name, feed))
+            })
+            .collect()
+    }
+}
+
+/// Returns the location of the value of an option in the options at the
+/// path, if the option is defined there
+fn locate_key(source: &Source, path: &[&str], (key, index): OptionKey) -> Option<Location> {
+    let doc = ImDocument::parse(&*source.content).ok()?;
+
+    let mut item = doc.as_item();
+    for key in path {
+        item = item.get(key)?;
+    }
+
+    item = item.get(key)?;
+    if let Some(i) = index {
+        item = item.get(i)?;
+    }
+
+    let offset = item.span()?.start;
+
+    Some(Location::new(&source.file, &source.content, offset))
+}
+
+/// Deserializes the options at the path of the source. Errors point to the
+/// invalid option, if it can be located, and to the options otherwise.
+fn deserialize<T>(options: Spanned<Table>, source: &Source, path: &[&str], item: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let location = Location::new(&source.file, &source.content, options.span().start);
+
+    T::deserialize(toml::Value::Table(options.into_inner())).map_err(|e| {
+        let err = Error::Config(e.message().to_string());
+        locate::<T>(source, path, e.message())
+            .unwrap_or(location)
+            .error(item, err)
+    })
+}
+
+/// Returns the location of the error in the options at the path, if these
+/// options cause the same error on their own.
+///
+/// The merged options of a feed do not know where their values come from.
+fn locate<T>(source: &Source, path: &[&str], message: &str) -> Option<Location>
+where
+    T: DeserializeOwned,
+{
+    let doc = ImDocument::parse(&*source.content).ok()?;
+
+    let mut item = doc.as_item();
+    for key in path {
+        item = item.get(key)?;
+    }
+
+    let err = T::deserialize(inline(item)?.into_deserializer()).err()?;
+    if err.message() != message {
+        return None;
+    }
+
+    // Internally tagged enums, like the sink options, report all errors at
+    // the table
+    let offset = match item.as_table_like() {
+        Some(table)
+            if err.span().map_or(true, |v| Some(v) == item.span())
+                && !message.starts_with("missing field") =>
+        {
+            let tag = table.get("type").and_then(inline);
+            match invalid_option::<T>(table, &mut Vec::new(), tag.as_ref(), message) {
+                Some(offset) => offset,
+                None => err.span()?.start,
+            }
+        }
+        _ => err.span()?.start,
+    };
+
+    Some(Location::new(&source.file, &source.content, offset))
+}
+
+/// Returns the offset of the key of the option that causes the error
+/// together with the tag alone. Nested tables are searched recursively.
+fn invalid_option<T>(
+    table: &dyn TableLike,
+    path: &mut Vec<String>,
+    tag: Option<&Value>,
+    message: &str,
+) -> Option<usize>
+where
+    T: DeserializeOwned,
+{
+    for (key, item) in table.iter() {
+        let Some(value) = inline(item) else {
+            continue;
+        };
+
+        path.push(key.to_string());
+
+        let mut options = value;
+        for key in path.iter().rev() {
+            let mut table = InlineTable::new();
+            table.insert(key, options);
+            options = Value::InlineTable(table);
+        }
+        if let (Some(tag), Value::InlineTable(options)) = (tag, &mut options) {
+            options.insert("type", tag.clone());
+        }
+
+        if T::deserialize(options.into_deserializer()).is_err_and(|e| e.message() == message) {
+            let nested = item
+                .as_table_like()
+                .and_then(|v| invalid_option::<T>(v, path, tag, message));
+            return nested.or_else(|| Some(table.key(key)?.span()?.start));
+        }
+
+        path.pop();
+    }
+
+    None
+}
+
+/// Returns the item as a value, tables are converted to inline tables
+fn inline(item: &Item) -> Option<Value> {
+    match item {
+        Item::Table(v) => Some(Value::InlineTable(v.clone().into_inline_table())),
+        Item::Value(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Feed {
+    pub url: String,
+    pub sink: Option<SinkRef>,
+    #[serde(default)]
+    pub sinks: Vec<SinkRef>,
+    #[serde(default, with = "humantime_serde")]
+    pub interval: Option<Duration>,
+    #[serde(default = "retry_limit_default")]
+    pub retry_limit: usize,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default = "backfill_default")]
+    pub backfill: bool,
+    #[serde(default)]
+    pub detection: Detection,
+    #[serde(default)]
+    pub undated: Undated,
+    pub filter: Option<Filter>,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+    /// Additional headers of the feed requests
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+impl Feed {
+    /// Returns the sinks of `sink` and `sinks` combined
+    pub fn sinks(&self) -> impl Iterator<Item = &SinkRef> {
+        self.sink.iter().chain(self.sinks.iter())
+    }
+
+    /// Checks the options and returns the key of the invalid option together
+    /// with the error
+    fn validate(&self) -> std::result::Result<(), (OptionKey, Box<Error>)> {
+        Url::parse(&self.url).map_err(|e| {
+            // The URL is not included, it may contain a secret
+            let err = Error::Config(format!("invalid URL: {e}"));
+            (("url", None), Box::new(err))
+        })?;
+
+        if self.sink.is_none() && self.sinks.is_empty() {
+            let err = Error::Config("no sink".to_string());
+            return Err((("sinks", None), Box::new(err)));
+        }
+
+        self.header_map()
+            .map_err(|e| (("headers", None), Box::new(e)))?;
+
+        self.retry.validate().map_err(|e| {
+            let err = Error::Config(format!("invalid retry policy: {e}"));
+            (("retry", None), Box::new(err))
+        })?;
+
+        let sinks = self.sink.iter().map(|v| (("sink", None), v)).chain(
+            self.sinks
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (("sinks", Some(i)), v)),
+        );
+
+        let mut ids = HashSet::new();
+        for (key, sink) in sinks {
+            if let SinkRef::Inline(sink) = sink {
+                sink.options.validate().map_err(|(_, e)| (key, e))?;
+
+                if !ids.insert(&sink.id) {
+                    let err = Error::Config(format!(
+                        "sinks with the same ID \"{}\", set a unique `id`",
+                        sink.id
+                    ));
+                    return Err((key, Box::new(err)));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn header_map(&self) -> Result<HeaderMap> {
+        self.headers
+            .iter()
+            .map(|(name, value)| {
+                let name = HeaderName::try_from(name)
+                    .map_err(|e| Error::Config(format!("invalid header name \"{name}\": {e}")))?;
+                let value = HeaderValue::try_from(value)
+                    .map_err(|e| Error::Config(format!("invalid value of header {name}: {e}")))?;
+
+                Ok((name, value))
+            })
+            .collect()
+    }
+}
+
+/// Key of a feed option and the index of an array element
+type OptionKey = (&'static str, Option<usize>);
+
+/// Either the name of a sink in the `sinks` table or inline sink options
+#[derive(Debug, Clone, PartialEq)]
+pub enum SinkRef {
+    Named(String),
+    Inline(Box<InlineSink>),
+}
+
+/// Sink options defined in a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineSink {
+    /// Identifies the delivery state of the sink. Defaults to a hash of the
+    /// options, so that the state is kept when sinks are reordered.
+    pub id: String,
+    pub options: SinkOptions,
+}
+
+impl<'de> Deserialize<'de> for SinkRef {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SinkRefVisitor;
+
+        impl<'de> Visitor<'de> for SinkRefVisitor {
+            type Value = SinkRef;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("sink name or sink options")
+            }
+
+            fn visit_str<E>(self, v: &str) -> std::result::Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(SinkRef::Named(v.to_string()))
+            }
+
+            fn visit_map<A>(self, map: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut table = Table::deserialize(de::value::MapAccessDeserializer::new(map))?;
+
+                let id = match table.remove("id") {
+                    Some(toml::Value::String(v)) if !v.is_empty() => v,
+                    Some(_) => return Err(de::Error::custom("sink id must be a non-empty string")),
+                    None => format!("{:016x}", fnv1a(table.to_string().as_bytes())),
+                };
+
+                let options = SinkOptions::deserialize(toml::Value::Table(table))
+                    .map_err(|e| de::Error::custom(e.message()))?;
+
+                Ok(SinkRef::Inline(Box::new(InlineSink { id, options })))
+            }
+        }
+
+        deserializer.deserialize_any(SinkRefVisitor)
+    }
+}
+
+/// 64-bit FNV-1a hash, which unlike the hasher of the standard library is
+/// stable across releases
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+const fn retry_limit_default() -> usize {
+    10
+}
+
+const fn backfill_default() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_errors() {
+        let content = r#"
+[sinks.a]
+type = "discord"
+url = "https://example.com"
+bogus = true
+
+[sinks.b]
+type = "matrix"
+homeserver = 1
+
+[sinks.c]
+type = "discord"
+url = "https://example.com"
+
+[sinks.c.template]
+bogus = "x"
+
+[sinks.d]
+type = "discord"
+"#;
+        let source = Source {
+            file: PathBuf::from("config.toml"),
+            content: content.into(),
+        };
+
+        let cases = [
+            ("a", Some((5, 1))),
+            ("b", Some((9, 1))),
+            ("c", Some((16, 1))),
+            // Missing options are reported at the table by the caller
+            ("d", None),
+        ];
+
+        for (sink, expected) in cases {
+            let path = ["sinks", sink];
+            let doc = ImDocument::parse(content).unwrap();
+            let item = &doc[path[0]][path[1]];
+            let err =
+                SinkOptions::deserialize(inline(item).unwrap().into_deserializer()).unwrap_err();
+
+            let location =
+                locate::<SinkOptions>(&source, &path, err.message()).map(|v| (v.line, v.column));
+            assert_eq!(location, expected, "{sink}");
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Substitution of environment variables and secret files in the config
+//!
+//! Substitutions are applied to the string values of the raw document, so
+//! that errors reported by the TOML parser still point to the right line.
+
+use crate::{error::Error, Result};
+
+use std::{env, fs, ops::Range};
+
+use toml_edit::{ImDocument, InlineTable, Item, Key, Table, Value};
+
+/// Fields that can be read from a file via `<field>_file`
+const SECRET_FIELDS: &[&str] = &["url", "token", "password", "access_token"];
+
+const FILE_SUFFIX: &str = "_file";
+
+/// Replaces `${VAR}` and `${VAR:-default}` in string values with the value of
+/// the environment variable and `<field>_file` keys of secret fields with
+/// `<field>` and the trimmed content of the file.
+///
+/// `$${` is replaced with a literal `${`. Documents that cannot be parsed are
+/// returned as is, the error is left to the deserializer.
+pub fn substitute(raw: &str) -> Result<String> {
+    let Ok(doc) = ImDocument::parse(raw) else {
+        return Ok(raw.to_string());
+    };
+
+    let mut edits = Vec::new();
+    Substitution {
+        raw,
+        edits: &mut edits,
+    }
+    .table(doc.as_table())?;
+
+    edits.sort_by_key(|(span, _)| span.start);
+
+    let mut result = raw.to_string();
+    for (span, text) in edits.into_iter().rev() {
+        result.replace_range(span, &text);
+    }
+
+    Ok(result)
+}
+
+struct Substitution<'a> {
+    raw: &'a str,
+    edits: &'a mut Vec<(Range<usize>, String)>,
+}
+
+impl Substitution<'_> {
+    fn table(&mut self, table: &Table) -> Result<()> {
+        for (name, _) in table.iter() {
+            let (key, item) = table.get_key_value(name).unwrap();
+
+            match item {
+                Item::Table(v) => self.table(v)?,
+                Item::ArrayOfTables(v) => {
+                    for table in v.iter() {
+                        self.table(table)?;
+                    }
+                }
+                Item::Value(v) => self.entry(key, v, |base| table.contains_key(base))?,
+                Item::None => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn inline_table(&mut self, table: &InlineTable) -> Result<()> {
+        for (name, _) in table.iter() {
+            let (key, item) = table.get_key_value(name).unwrap();
+
+            if let Item::Value(v) = item {
+                self.entry(key, v, |base| table.contains_key(base))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn entry(
+        &mut self,
+        key: &Key,
+        value: &Value,
+        contains_key: impl Fn(&str) -> bool,
+    ) -> Result<()> {
+        let secret = key
+            .get()
+            .strip_suffix(FILE_SUFFIX)
+            .filter(|base| SECRET_FIELDS.contains(base));
+
+        let Some(base) = secret else {
+            return self.value(value);
+        };
+
+        if contains_key(base) {
+            return Err(self.error(
+                key.span(),
+                format!("`{base}` and `{}` are mutually exclusive", key.get()),
+            ));
+        }
+
+        let Value::String(path) = value else {
+            return Err(self.error(value.span(), format!("`{}` must be a path", key.get())));
+        };
+
+        let path = self.interpolate(path.value(), value.span())?;
+        let secret = fs::read_to_string(&path)
+            .map_err(|e| self.error(value.span(), format!("cannot read \"{path}\": {e}")))?;
+
+        self.edit(key.span(), base.to_string());
+        self.edit(value.span(), encode(secret.trim_end_matches(['\n', '\r'])));
+
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::String(v) if v.value().contains('$') => {
+                let text = self.interpolate(v.value(), value.span())?;
+                if text != *v.value() {
+                    self.edit(value.span(), encode(&text));
+                }
+            }
+            Value::Array(v) => {
+                for value in v.iter() {
+                    self.value(value)?;
+                }
+            }
+            Value::InlineTable(v) => self.inline_table(v)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn interpolate(&self, s: &str, span: Option<Range<usize>>) -> Result<String> {
+        interpolate(s).map_err(|e| self.error(span, e))
+    }
+
+    fn edit(&mut self, span: Option<Range<usize>>, text: String) {
+        if let Some(span) = span {
+            self.edits.push((span, text));
+        }
+    }
+
+    fn error(&self, span: Option<Range<usize>>, msg: String) -> Error {
+        match span {
+            Some(span) => {
+                let (line, column) = super::line_column(self.raw, span.start);
+                Error::Config(format!("{msg} at line {line}, column {column}"))
+            }
+            None => Error::Config(msg),
+        }
+    }
+}
+
+fn interpolate(s: &str) -> std::result::Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find('$') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if let Some(v) = rest.strip_prefix("$${") {
+            result.push_str("${");
+            rest = v;
+        } else if let Some(v) = rest.strip_prefix("${") {
+            let end = v
+                .find('}')
+                .ok_or_else(|| "unclosed variable, expected `}`".to_string())?;
+            let (name, default) = match v[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&v[..end], None),
+            };
+
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("invalid variable name \"{name}\""));
+            }
+
+            // Like in shells, the default is also used for empty variables
+            match (env::var(name).ok().filter(|v| !v.is_empty()), default) {
+                (Some(value), _) => result.push_str(&value),
+                (None, Some(default)) => result.push_str(default),
+                (None, None) => return Err(format!("environment variable {name} is not set")),
+            }
+
+            rest = &v[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// Encodes the string as a single-line TOML basic string
+fn encode(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04X}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_variables() {
+        env::set_var("RSS_FORWARDER_TEST_SET", "value");
+        env::set_var("RSS_FORWARDER_TEST_EMPTY", "");
+        env::remove_var("RSS_FORWARDER_TEST_UNSET");
+
+        let cases = [
+            ("plain", Ok("plain")),
+            ("${RSS_FORWARDER_TEST_SET}", Ok("value")),
+            ("a/${RSS_FORWARDER_TEST_SET}/b", Ok("a/value/b")),
+            ("${RSS_FORWARDER_TEST_SET:-default}", Ok("value")),
+            ("${RSS_FORWARDER_TEST_UNSET:-default}", Ok("default")),
+            ("${RSS_FORWARDER_TEST_EMPTY:-default}", Ok("default")),
+            ("${RSS_FORWARDER_TEST_UNSET:-}", Ok("")),
+            (
+                "$${RSS_FORWARDER_TEST_SET}",
+                Ok("${RSS_FORWARDER_TEST_SET}"),
+            ),
+            ("$5 and $", Ok("$5 and $")),
+            (
+                "${RSS_FORWARDER_TEST_UNSET}",
+                Err("environment variable RSS_FORWARDER_TEST_UNSET is not set"),
+            ),
+            (
+                "${RSS_FORWARDER_TEST_SET",
+                Err("unclosed variable, expected `}`"),
+            ),
+            ("${}", Err("invalid variable name \"\"")),
+            ("${A-B}", Err("invalid variable name \"A-B\"")),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(
+                interpolate(value),
+                expected.map(str::to_string).map_err(str::to_string),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn encode_strings() {
+        let cases = [
+            ("", r#""""#),
+            ("plain", r#""plain""#),
+            (r#"a "quoted" \ path"#, r#""a \"quoted\" \\ path""#),
+            ("line\r\nbreak\ttab", r#""line\r\nbreak\ttab""#),
+            ("bell\u{7}", r#""bell\u0007""#),
+            ("ümlaut", r#""ümlaut""#),
+        ];
+
+        for (value, expected) in cases {
+            let encoded = encode(value);
+            assert_eq!(encoded, expected, "{value:?}");
+
+            let parsed: toml::Table = toml::from_str(&format!("v = {encoded}")).unwrap();
+            assert_eq!(parsed["v"].as_str(), Some(value), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn substitute_documents() {
+        env::set_var("RSS_FORWARDER_TEST_URL", "https://example.com/\"hook\"");
+
+        let path = env::temp_dir().join(format!("rss-forwarder-test-{}", std::process::id()));
+        fs::write(&path, "secret\\token\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let cases = [
+            ("a = \"$${b}\"\n", Ok("a = \"${b}\"\n".to_string())),
+            (
+                "url = \"${RSS_FORWARDER_TEST_URL}\" # comment\n",
+                Ok("url = \"https://example.com/\\\"hook\\\"\" # comment\n".to_string()),
+            ),
+            (
+                "[s]\nlist = [\"${RSS_FORWARDER_TEST_UNSET:-x}\", 1]\n",
+                Ok("[s]\nlist = [\"x\", 1]\n".to_string()),
+            ),
+            (
+                &*format!("sink = {{ token_file = \"{path}\" }}\n"),
+                Ok("sink = { token = \"secret\\\\token\" }\n".to_string()),
+            ),
+            // Only secret fields are read from files
+            (
+                "name_file = \"/nonexistent\"\n",
+                Ok("name_file = \"/nonexistent\"\n".to_string()),
+            ),
+            (
+                "url = \"a\"\nurl_file = \"b\"\n",
+                Err("`url` and `url_file` are mutually exclusive at line 2, column 1"),
+            ),
+            (
+                "password_file = 1\n",
+                Err("`password_file` must be a path at line 1, column 17"),
+            ),
+            (
+                "\n[a]\nb = \"${c\"\n",
+                Err("unclosed variable, expected `}` at line 3, column 5"),
+            ),
+            // Left to the deserializer
+            ("a = \"${\n", Ok("a = \"${\n".to_string())),
+        ];
+
+        for (value, expected) in cases {
+            let result = substitute(value).map_err(|e| match e {
+                Error::Config(msg) => msg,
+                e => panic!("unexpected error: {e}"),
+            });
+            assert_eq!(result, expected.map_err(str::to_string), "{value:?}");
+        }
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -12,8 +12,27 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("request error: {0}")]
     Request(#[from] reqwest::Error),
+    #[error(
+        "rate limited by {host}{}",
+        retry_after.map(|v| format!(", retry after {v:?}")).unwrap_or_default()
+    )]
+    RateLimited {
+        /// Only the host, the URL might contain a token
+        host: String,
+        retry_after: Option<std::time::Duration>,
+    },
+    #[error("config error: {0}")]
+    Config(String),
     #[error("toml error: {0}")]
     Toml(#[from] toml::de::Error),
+    #[error("smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("email error: {0}")]
+    Email(#[from] lettre::error::Error),
+    #[error("invalid email address {0}")]
+    Address(String),
+    #[error("template error: {0}")]
+    Template(#[from] minijinja::Error),
     #[error("argument error: {0}")]
     Argument(#[from] pico_args::Error),
 }
@@ -26,6 +45,14 @@ pub enum FeedError {
     Rss(#[from] rss::Error),
     #[error("atom error: {0}")]
     Atom(#[from] atom_syndication::Error),
+    #[error("rdf error: {0}")]
+    Rdf(String),
+    #[error("xml error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("json feed error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("html2text error: {0}")]
     Html2Text(#[from] html2text::Error),
 }
@@ -0,0 +1,247 @@
+//! Lenient parsing of the date formats found in the wild
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime};
+
+const RFC2822_FORMATS: &[&str] = &[
+    "%d %b %Y %H:%M:%S %z",
+    "%d %b %Y %H:%M %z",
+    "%d %b %y %H:%M:%S %z",
+    "%d %b %y %H:%M %z",
+    "%d %B %Y %H:%M:%S %z",
+    "%d %B %Y %H:%M %z",
+    "%b %d %Y %H:%M:%S %z",
+];
+
+const RFC2822_NAIVE_FORMATS: &[&str] = &[
+    "%d %b %Y %H:%M:%S",
+    "%d %b %Y %H:%M",
+    "%d %B %Y %H:%M:%S",
+    "%d %B %Y %H:%M",
+];
+
+const ISO8601_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%dT%H:%M%z",
+];
+
+const ISO8601_NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Time zone abbreviations and their offset in minutes.
+///
+/// Ambiguous abbreviations (e.g. `IST`) are intentionally left out.
+const TIME_ZONES: &[(&str, i32)] = &[
+    ("UT", 0),
+    ("UTC", 0),
+    ("GMT", 0),
+    ("Z", 0),
+    ("WET", 0),
+    ("WEST", 60),
+    ("BST", 60),
+    ("CET", 60),
+    ("CEST", 120),
+    ("MET", 60),
+    ("MEST", 120),
+    ("EET", 120),
+    ("EEST", 180),
+    ("MSK", 180),
+    ("HKT", 480),
+    ("SGT", 480),
+    ("AWST", 480),
+    ("JST", 540),
+    ("KST", 540),
+    ("ACST", 570),
+    ("ACDT", 630),
+    ("AEST", 600),
+    ("AEDT", 660),
+    ("NZST", 720),
+    ("NZDT", 780),
+    ("NST", -210),
+    ("NDT", -150),
+    ("AST", -240),
+    ("ADT", -180),
+    ("EST", -300),
+    ("EDT", -240),
+    ("CST", -360),
+    ("CDT", -300),
+    ("MST", -420),
+    ("MDT", -360),
+    ("PST", -480),
+    ("PDT", -420),
+    ("AKST", -540),
+    ("AKDT", -480),
+    ("HST", -600),
+];
+
+const WEEKDAYS: &[&str] = &["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Parses a date in RFC 2822 or ISO 8601 format.
+///
+/// Apart from the strict formats, common deviations like missing or wrong
+/// weekdays, full month names, named time zones and missing time zones are
+/// accepted. Dates without a time zone are assumed to be UTC.
+pub fn parse(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+
+    if let Ok(v) = DateTime::parse_from_rfc2822(value) {
+        return Some(v);
+    }
+
+    if let Ok(v) = DateTime::parse_from_rfc3339(value) {
+        return Some(v);
+    }
+
+    parse_rfc2822_lenient(value).or_else(|| parse_iso8601_lenient(value))
+}
+
+fn parse_rfc2822_lenient(value: &str) -> Option<DateTime<FixedOffset>> {
+    let mut parts: Vec<String> = value
+        .split_whitespace()
+        .map(|s| s.trim_end_matches(',').to_string())
+        .collect();
+
+    // The weekday is redundant and often wrong, so it is ignored
+    if parts.first().is_some_and(|v| is_weekday(v)) {
+        parts.remove(0);
+    }
+
+    if let Some(offset) = parts.last().and_then(|v| time_zone_offset(v)) {
+        *parts.last_mut().unwrap() = offset;
+    }
+
+    let value = parts.join(" ");
+
+    parse_with_formats(&value, RFC2822_FORMATS, RFC2822_NAIVE_FORMATS)
+}
+
+fn parse_iso8601_lenient(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = match value.strip_suffix('Z') {
+        Some(v) => format!("{v}+00:00"),
+        None => value.to_string(),
+    };
+
+    if let Some(v) = parse_with_formats(&value, ISO8601_FORMATS, ISO8601_NAIVE_FORMATS) {
+        return Some(v);
+    }
+
+    NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .ok()
+        .and_then(|v| v.and_hms_opt(0, 0, 0))
+        .map(|v| v.and_utc().fixed_offset())
+}
+
+fn parse_with_formats(
+    value: &str,
+    formats: &[&str],
+    naive_formats: &[&str],
+) -> Option<DateTime<FixedOffset>> {
+    formats
+        .iter()
+        .find_map(|f| DateTime::parse_from_str(value, f).ok())
+        .or_else(|| {
+            naive_formats
+                .iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+                .map(|v| v.and_utc().fixed_offset())
+        })
+}
+
+fn is_weekday(value: &str) -> bool {
+    let value = value.to_ascii_lowercase();
+    value.chars().all(|c| c.is_ascii_alphabetic()) && WEEKDAYS.iter().any(|v| value.starts_with(v))
+}
+
+fn time_zone_offset(value: &str) -> Option<String> {
+    let value = value.to_ascii_uppercase();
+
+    TIME_ZONES
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, offset)| {
+            let sign = if *offset < 0 { '-' } else { '+' };
+            let offset = offset.abs();
+            format!("{sign}{:02}{:02}", offset / 60, offset % 60)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_formats() {
+        let cases = [
+            // Strict formats
+            (
+                "Tue, 10 Jun 2003 04:00:00 GMT",
+                Some("2003-06-10T04:00:00+00:00"),
+            ),
+            (
+                "Tue, 10 Jun 2003 04:00:00 +0200",
+                Some("2003-06-10T04:00:00+02:00"),
+            ),
+            ("2003-06-10T04:00:00Z", Some("2003-06-10T04:00:00+00:00")),
+            (
+                "2003-06-10T04:00:00.5-05:00",
+                Some("2003-06-10T04:00:00.500-05:00"),
+            ),
+            // Lenient RFC 2822
+            (
+                "Wed, 10 Jun 2003 04:00:00 GMT",
+                Some("2003-06-10T04:00:00+00:00"),
+            ),
+            (
+                "Tuesday, 10 Jun 2003 04:00:00 GMT",
+                Some("2003-06-10T04:00:00+00:00"),
+            ),
+            (
+                "10 June 2003 04:00:00 CEST",
+                Some("2003-06-10T04:00:00+02:00"),
+            ),
+            ("10 Jun 2003 04:00 pdt", Some("2003-06-10T04:00:00-07:00")),
+            (
+                "10 Jun 03 04:00:00 +0000",
+                Some("2003-06-10T04:00:00+00:00"),
+            ),
+            (
+                "Jun 10 2003 04:00:00 +0000",
+                Some("2003-06-10T04:00:00+00:00"),
+            ),
+            ("10 Jun 2003 04:00:00", Some("2003-06-10T04:00:00+00:00")),
+            (
+                "10 Jun 2003 04:00:00 NST",
+                Some("2003-06-10T04:00:00-03:30"),
+            ),
+            // Lenient ISO 8601
+            (
+                "2003-06-10 04:00:00+02:00",
+                Some("2003-06-10T04:00:00+02:00"),
+            ),
+            ("2003-06-10T04:00+0200", Some("2003-06-10T04:00:00+02:00")),
+            ("2003-06-10T04:00:00", Some("2003-06-10T04:00:00+00:00")),
+            ("2003-06-10T04:00", Some("2003-06-10T04:00:00+00:00")),
+            ("2003-06-10", Some("2003-06-10T00:00:00+00:00")),
+            (
+                "  2003-06-10T04:00:00Z\n",
+                Some("2003-06-10T04:00:00+00:00"),
+            ),
+            // Invalid
+            ("", None),
+            ("yesterday", None),
+            ("10 Jun 2003 04:00:00 IST", None),
+            ("2003-13-10", None),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(
+                parse(value).map(|v| v.to_rfc3339()),
+                expected.map(str::to_string),
+                "{value:?}"
+            );
+        }
+    }
+}
@@ -1,28 +1,45 @@
+use super::{date, json, rdf};
+
 use atom_syndication::TextType;
 use chrono::{DateTime, FixedOffset};
 use serde::Serialize;
 
 pub trait FeedItem<'a>: Sync {
-    fn title(&'a self) -> Option<&str>;
+    fn title(&'a self) -> Option<&'a str>;
 
     fn title_as_text(&'a self) -> Result<Option<String>, html2text::Error>;
 
-    fn description(&'a self) -> Option<&str>;
+    fn description(&'a self) -> Option<&'a str>;
 
     fn description_as_text(&'a self) -> Result<Option<String>, html2text::Error>;
 
-    fn content(&'a self) -> Option<&str>;
+    /// Whether the description is HTML, otherwise it is plain text
+    fn description_is_html(&'a self) -> bool;
+
+    fn content(&'a self) -> Option<&'a str>;
 
     fn content_as_text(&'a self) -> Result<Option<String>, html2text::Error>;
 
-    fn link(&'a self) -> Option<&str>;
+    /// Whether the content is HTML, otherwise it is plain text
+    fn content_is_html(&'a self) -> bool;
+
+    fn link(&'a self) -> Option<&'a str>;
+
+    /// Unique identifier of the item
+    fn id(&'a self) -> Option<&'a str>;
 
-    fn date(&'a self) -> DateTime<FixedOffset>;
+    /// Publication date of the item, if it has a valid one
+    fn date(&'a self) -> Option<DateTime<FixedOffset>>;
 
-    fn authors(&'a self) -> Vec<Author>;
+    fn authors(&'a self) -> Vec<Author<'a>>;
+
+    fn categories(&'a self) -> Vec<&'a str>;
+
+    /// Attached media files, e.g. podcast episodes
+    fn enclosures(&'a self) -> Vec<Enclosure<'a>>;
 
     /// Feed metadata
-    fn source(&'a self) -> Option<&Source>;
+    fn source(&'a self) -> Option<&'a Source<'a>>;
 }
 
 pub trait TryFromItem<'a, T>
@@ -60,6 +77,11 @@ impl<'a> FeedItem<'a> for rss::Item {
             .transpose()
     }
 
+    #[inline]
+    fn description_is_html(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn content(&self) -> Option<&str> {
         self.content()
@@ -72,18 +94,32 @@ impl<'a> FeedItem<'a> for rss::Item {
             .transpose()
     }
 
+    #[inline]
+    fn content_is_html(&self) -> bool {
+        true
+    }
+
     #[inline]
     fn link(&self) -> Option<&str> {
         self.link()
     }
 
     #[inline]
-    fn date(&self) -> DateTime<FixedOffset> {
-        DateTime::parse_from_rfc2822(self.pub_date().expect("missing pub date")).unwrap()
+    fn id(&self) -> Option<&str> {
+        self.guid().map(|v| v.value()).or_else(|| self.link())
     }
 
     #[inline]
-    fn authors(&self) -> Vec<Author> {
+    fn date(&self) -> Option<DateTime<FixedOffset>> {
+        self.pub_date().and_then(date::parse).or_else(|| {
+            self.dublin_core_ext()
+                .and_then(|v| v.dates().first())
+                .and_then(|v| date::parse(v))
+        })
+    }
+
+    #[inline]
+    fn authors(&self) -> Vec<Author<'_>> {
         match self.author() {
             Some(v) => vec![Author {
                 name: v,
@@ -94,7 +130,24 @@ impl<'a> FeedItem<'a> for rss::Item {
         }
     }
 
-    fn source(&'a self) -> Option<&Source> {
+    #[inline]
+    fn categories(&self) -> Vec<&str> {
+        self.categories().iter().map(|v| v.name()).collect()
+    }
+
+    #[inline]
+    fn enclosures(&self) -> Vec<Enclosure<'_>> {
+        match self.enclosure() {
+            Some(v) => vec![Enclosure {
+                url: v.url(),
+                mime_type: Some(v.mime_type()).filter(|s| !s.is_empty()),
+                length: v.length().parse().ok(),
+            }],
+            None => Vec::default(),
+        }
+    }
+
+    fn source(&'a self) -> Option<&'a Source<'a>> {
         None
     }
 }
@@ -133,6 +186,11 @@ impl<'a> FeedItem<'a> for atom_syndication::Entry {
         }
     }
 
+    #[inline]
+    fn description_is_html(&self) -> bool {
+        self.summary().is_some_and(|v| v.r#type != TextType::Text)
+    }
+
     #[inline]
     fn content(&self) -> Option<&str> {
         match self.content() {
@@ -152,6 +210,14 @@ impl<'a> FeedItem<'a> for atom_syndication::Entry {
         }
     }
 
+    #[inline]
+    fn content_is_html(&self) -> bool {
+        matches!(
+            self.content().and_then(|v| v.content_type()),
+            Some("html" | "xhtml" | "text/html")
+        )
+    }
+
     #[inline]
     fn link(&self) -> Option<&str> {
         self.links()
@@ -160,12 +226,17 @@ impl<'a> FeedItem<'a> for atom_syndication::Entry {
             .map(|s| s.href())
     }
 
-    fn date(&self) -> DateTime<FixedOffset> {
-        self.updated
+    #[inline]
+    fn id(&self) -> Option<&str> {
+        Some(self.id())
+    }
+
+    fn date(&self) -> Option<DateTime<FixedOffset>> {
+        Some(self.updated)
     }
 
     #[inline]
-    fn authors(&self) -> Vec<Author> {
+    fn authors(&self) -> Vec<Author<'_>> {
         self.authors()
             .iter()
             .map(|v| Author {
@@ -176,7 +247,219 @@ impl<'a> FeedItem<'a> for atom_syndication::Entry {
             .collect()
     }
 
-    fn source(&'a self) -> Option<&Source> {
+    #[inline]
+    fn categories(&self) -> Vec<&str> {
+        self.categories().iter().map(|v| v.term()).collect()
+    }
+
+    #[inline]
+    fn enclosures(&self) -> Vec<Enclosure<'_>> {
+        self.links()
+            .iter()
+            .filter(|v| v.rel() == "enclosure")
+            .map(|v| Enclosure {
+                url: v.href(),
+                mime_type: v.mime_type(),
+                length: v.length().and_then(|s| s.parse().ok()),
+            })
+            .collect()
+    }
+
+    fn source(&'a self) -> Option<&'a Source<'a>> {
+        None
+    }
+}
+
+impl<'a> FeedItem<'a> for json::Item {
+    #[inline]
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    #[inline]
+    fn title_as_text(&self) -> Result<Option<String>, html2text::Error> {
+        Ok(self.title.clone())
+    }
+
+    #[inline]
+    fn description(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    #[inline]
+    fn description_as_text(&self) -> Result<Option<String>, html2text::Error> {
+        Ok(self.summary.clone())
+    }
+
+    #[inline]
+    fn description_is_html(&self) -> bool {
+        false
+    }
+
+    #[inline]
+    fn content(&self) -> Option<&str> {
+        self.content_html
+            .as_deref()
+            .or(self.content_text.as_deref())
+    }
+
+    #[inline]
+    fn content_as_text(&self) -> Result<Option<String>, html2text::Error> {
+        match (&self.content_text, &self.content_html) {
+            (Some(v), _) => Ok(Some(v.clone())),
+            (None, Some(v)) => html2text::from_read(v.as_bytes(), usize::MAX).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+
+    #[inline]
+    fn content_is_html(&self) -> bool {
+        self.content_html.is_some()
+    }
+
+    #[inline]
+    fn link(&self) -> Option<&str> {
+        self.url.as_deref().or(self.external_url.as_deref())
+    }
+
+    #[inline]
+    fn id(&self) -> Option<&str> {
+        Some(&self.id)
+    }
+
+    #[inline]
+    fn date(&self) -> Option<DateTime<FixedOffset>> {
+        self.date_published
+            .as_deref()
+            .or(self.date_modified.as_deref())
+            .and_then(date::parse)
+    }
+
+    #[inline]
+    fn authors(&self) -> Vec<Author<'_>> {
+        self.authors
+            .iter()
+            .chain(self.author.iter())
+            .filter_map(|v| {
+                Some(Author {
+                    name: v.name.as_deref().or(v.url.as_deref())?,
+                    email: None,
+                    uri: v.url.as_deref(),
+                })
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn categories(&self) -> Vec<&str> {
+        self.tags.iter().map(|v| v.as_str()).collect()
+    }
+
+    #[inline]
+    fn enclosures(&self) -> Vec<Enclosure<'_>> {
+        self.attachments
+            .iter()
+            .map(|v| Enclosure {
+                url: &v.url,
+                mime_type: Some(&v.mime_type),
+                length: v.size_in_bytes,
+            })
+            .collect()
+    }
+
+    fn source(&'a self) -> Option<&'a Source<'a>> {
+        None
+    }
+}
+
+impl<'a> FeedItem<'a> for rdf::Item {
+    #[inline]
+    fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    #[inline]
+    fn title_as_text(&self) -> Result<Option<String>, html2text::Error> {
+        self.title
+            .as_ref()
+            .map(|s| html2text::from_read(s.as_bytes(), usize::MAX))
+            .transpose()
+    }
+
+    #[inline]
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    #[inline]
+    fn description_as_text(&self) -> Result<Option<String>, html2text::Error> {
+        self.description
+            .as_ref()
+            .map(|s| html2text::from_read(s.as_bytes(), usize::MAX))
+            .transpose()
+    }
+
+    #[inline]
+    fn description_is_html(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn content(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
+
+    #[inline]
+    fn content_as_text(&self) -> Result<Option<String>, html2text::Error> {
+        self.content
+            .as_ref()
+            .map(|s| html2text::from_read(s.as_bytes(), usize::MAX))
+            .transpose()
+    }
+
+    #[inline]
+    fn content_is_html(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
+    #[inline]
+    fn id(&self) -> Option<&str> {
+        self.about.as_deref().or(self.link.as_deref())
+    }
+
+    #[inline]
+    fn date(&self) -> Option<DateTime<FixedOffset>> {
+        self.date.as_deref().and_then(date::parse)
+    }
+
+    #[inline]
+    fn authors(&self) -> Vec<Author<'_>> {
+        self.creators
+            .iter()
+            .map(|v| Author {
+                name: v,
+                email: None,
+                uri: None,
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn categories(&self) -> Vec<&str> {
+        self.subjects.iter().map(|v| v.as_str()).collect()
+    }
+
+    #[inline]
+    fn enclosures(&self) -> Vec<Enclosure<'_>> {
+        Vec::default()
+    }
+
+    fn source(&'a self) -> Option<&'a Source<'a>> {
         None
     }
 }
@@ -188,8 +471,18 @@ pub struct Author<'a> {
     pub uri: Option<&'a str>,
 }
 
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Enclosure<'a> {
+    pub url: &'a str,
+    pub mime_type: Option<&'a str>,
+    /// Size in bytes
+    pub length: Option<u64>,
+}
+
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct Source<'a> {
+    /// Configured name of the feed
+    pub name: &'a str,
     pub title: &'a str,
     pub url: Option<&'a str>,
 }
@@ -199,19 +492,47 @@ pub enum Item<'a> {
     Rss {
         source: Source<'a>,
         item: &'a rss::Item,
+        fallback_date: Option<DateTime<FixedOffset>>,
     },
     Atom {
         source: Source<'a>,
         entry: &'a atom_syndication::Entry,
+        fallback_date: Option<DateTime<FixedOffset>>,
+    },
+    Json {
+        source: Source<'a>,
+        item: &'a json::Item,
+        fallback_date: Option<DateTime<FixedOffset>>,
+    },
+    Rdf {
+        source: Source<'a>,
+        item: &'a rdf::Item,
+        fallback_date: Option<DateTime<FixedOffset>>,
     },
 }
 
+impl Item<'_> {
+    /// Sets the date that is used if the item has no date itself
+    #[must_use]
+    pub fn with_fallback_date(mut self, date: DateTime<FixedOffset>) -> Self {
+        match &mut self {
+            Item::Rss { fallback_date, .. }
+            | Item::Atom { fallback_date, .. }
+            | Item::Json { fallback_date, .. }
+            | Item::Rdf { fallback_date, .. } => *fallback_date = Some(date),
+        }
+        self
+    }
+}
+
 impl<'a> FeedItem<'a> for Item<'a> {
     #[inline]
     fn title(&self) -> Option<&str> {
         match self {
             Item::Rss { item, .. } => <rss::Item as FeedItem>::title(item),
             Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::title(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::title(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::title(item),
         }
     }
 
@@ -220,6 +541,8 @@ impl<'a> FeedItem<'a> for Item<'a> {
         match self {
             Item::Rss { item, .. } => <rss::Item as FeedItem>::title_as_text(item),
             Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::title_as_text(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::title_as_text(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::title_as_text(item),
         }
     }
 
@@ -228,6 +551,8 @@ impl<'a> FeedItem<'a> for Item<'a> {
         match self {
             Item::Rss { item, .. } => <rss::Item as FeedItem>::description(item),
             Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::description(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::description(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::description(item),
         }
     }
 
@@ -238,6 +563,20 @@ impl<'a> FeedItem<'a> for Item<'a> {
             Item::Atom { entry, .. } => {
                 <atom_syndication::Entry as FeedItem>::description_as_text(entry)
             }
+            Item::Json { item, .. } => <json::Item as FeedItem>::description_as_text(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::description_as_text(item),
+        }
+    }
+
+    #[inline]
+    fn description_is_html(&self) -> bool {
+        match self {
+            Item::Rss { item, .. } => <rss::Item as FeedItem>::description_is_html(item),
+            Item::Atom { entry, .. } => {
+                <atom_syndication::Entry as FeedItem>::description_is_html(entry)
+            }
+            Item::Json { item, .. } => <json::Item as FeedItem>::description_is_html(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::description_is_html(item),
         }
     }
 
@@ -246,6 +585,8 @@ impl<'a> FeedItem<'a> for Item<'a> {
         match self {
             Item::Rss { item, .. } => <rss::Item as FeedItem>::content(item),
             Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::content(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::content(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::content(item),
         }
     }
 
@@ -256,6 +597,20 @@ impl<'a> FeedItem<'a> for Item<'a> {
             Item::Atom { entry, .. } => {
                 <atom_syndication::Entry as FeedItem>::content_as_text(entry)
             }
+            Item::Json { item, .. } => <json::Item as FeedItem>::content_as_text(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::content_as_text(item),
+        }
+    }
+
+    #[inline]
+    fn content_is_html(&self) -> bool {
+        match self {
+            Item::Rss { item, .. } => <rss::Item as FeedItem>::content_is_html(item),
+            Item::Atom { entry, .. } => {
+                <atom_syndication::Entry as FeedItem>::content_is_html(entry)
+            }
+            Item::Json { item, .. } => <json::Item as FeedItem>::content_is_html(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::content_is_html(item),
         }
     }
 
@@ -264,30 +619,84 @@ impl<'a> FeedItem<'a> for Item<'a> {
         match self {
             Item::Rss { item, .. } => <rss::Item as FeedItem>::link(item),
             Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::link(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::link(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::link(item),
+        }
+    }
+
+    #[inline]
+    fn id(&self) -> Option<&str> {
+        match self {
+            Item::Rss { item, .. } => <rss::Item as FeedItem>::id(item),
+            Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::id(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::id(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::id(item),
         }
     }
 
     #[inline]
-    fn date(&self) -> DateTime<FixedOffset> {
+    fn date(&self) -> Option<DateTime<FixedOffset>> {
         match self {
-            Item::Rss { item, .. } => <rss::Item as FeedItem>::date(item),
-            Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::date(entry),
+            Item::Rss {
+                item,
+                fallback_date,
+                ..
+            } => <rss::Item as FeedItem>::date(item).or(*fallback_date),
+            Item::Atom {
+                entry,
+                fallback_date,
+                ..
+            } => <atom_syndication::Entry as FeedItem>::date(entry).or(*fallback_date),
+            Item::Json {
+                item,
+                fallback_date,
+                ..
+            } => <json::Item as FeedItem>::date(item).or(*fallback_date),
+            Item::Rdf {
+                item,
+                fallback_date,
+                ..
+            } => <rdf::Item as FeedItem>::date(item).or(*fallback_date),
         }
     }
 
     #[inline]
-    fn authors(&self) -> Vec<Author> {
+    fn authors(&self) -> Vec<Author<'_>> {
         match self {
             Item::Rss { item, .. } => <rss::Item as FeedItem>::authors(item),
             Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::authors(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::authors(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::authors(item),
+        }
+    }
+
+    #[inline]
+    fn categories(&self) -> Vec<&str> {
+        match self {
+            Item::Rss { item, .. } => <rss::Item as FeedItem>::categories(item),
+            Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::categories(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::categories(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::categories(item),
+        }
+    }
+
+    #[inline]
+    fn enclosures(&self) -> Vec<Enclosure<'_>> {
+        match self {
+            Item::Rss { item, .. } => <rss::Item as FeedItem>::enclosures(item),
+            Item::Atom { entry, .. } => <atom_syndication::Entry as FeedItem>::enclosures(entry),
+            Item::Json { item, .. } => <json::Item as FeedItem>::enclosures(item),
+            Item::Rdf { item, .. } => <rdf::Item as FeedItem>::enclosures(item),
         }
     }
 
     #[inline]
-    fn source(&'a self) -> Option<&Source> {
+    fn source(&'a self) -> Option<&'a Source<'a>> {
         match self {
             Item::Rss { source, .. } => Some(source),
             Item::Atom { source, .. } => Some(source),
+            Item::Json { source, .. } => Some(source),
+            Item::Rdf { source, .. } => Some(source),
         }
     }
 }
@@ -0,0 +1,156 @@
+//! [JSON Feed](https://www.jsonfeed.org/version/1.1/) version 1.0 and 1.1
+
+use crate::sink::{truncate, Length};
+
+use serde::{Deserialize, Deserializer};
+
+/// Maximum length of the title of untitled items
+const FALLBACK_TITLE_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct Feed {
+    pub title: String,
+    pub home_page_url: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_items")]
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Item {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub id: String,
+    pub url: Option<String>,
+    pub external_url: Option<String>,
+    pub title: Option<String>,
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub summary: Option<String>,
+    pub date_published: Option<String>,
+    pub date_modified: Option<String>,
+    /// Deprecated in version 1.1 in favour of `authors`
+    pub author: Option<Author>,
+    #[serde(default)]
+    pub authors: Vec<Author>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl Item {
+    /// Returns the first line of the summary or content, or the URL
+    fn fallback_title(&self) -> Option<String> {
+        let text = self
+            .summary
+            .clone()
+            .or_else(|| self.content_text.clone())
+            .or_else(|| {
+                let html = self.content_html.as_ref()?;
+                html2text::from_read(html.as_bytes(), usize::MAX).ok()
+            })
+            .unwrap_or_default();
+
+        match text.lines().map(str::trim).find(|v| !v.is_empty()) {
+            Some(line) => Some(truncate(line, FALLBACK_TITLE_LIMIT, Length::Chars)),
+            None => self.url.clone().or_else(|| self.external_url.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Author {
+    pub name: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Attachment {
+    pub url: String,
+    pub mime_type: String,
+    pub size_in_bytes: Option<u64>,
+}
+
+/// Titles are optional and often left out by microblogs, so untitled items
+/// get a title from their text.
+fn deserialize_items<'de, D>(deserializer: D) -> Result<Vec<Item>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut items = Vec::<Item>::deserialize(deserializer)?;
+
+    for item in items.iter_mut() {
+        if item.title.as_deref().map_or(true, |v| v.trim().is_empty()) {
+            item.title = item.fallback_title();
+        }
+    }
+
+    Ok(items)
+}
+
+/// The spec requires a string, but some feeds use numbers.
+fn deserialize_id<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        String(String),
+        Number(serde_json::Number),
+    }
+
+    Ok(match Id::deserialize(deserializer)? {
+        Id::String(v) => v,
+        Id::Number(v) => v.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_title() {
+        let long = "a".repeat(FALLBACK_TITLE_LIMIT + 1);
+        let truncated = format!("{}…", "a".repeat(FALLBACK_TITLE_LIMIT - 1));
+
+        let cases = [
+            (
+                r#"{"id": "1", "title": "Title", "summary": "Summary"}"#,
+                Some("Title"),
+            ),
+            (
+                r#"{"id": "1", "title": " ", "summary": "Summary"}"#,
+                Some("Summary"),
+            ),
+            (
+                r#"{"id": "1", "summary": "Summary", "content_text": "Text"}"#,
+                Some("Summary"),
+            ),
+            (
+                r#"{"id": "1", "content_text": "\n  First line \nSecond line"}"#,
+                Some("First line"),
+            ),
+            (
+                r#"{"id": "1", "content_html": "<p>Hello <b>world</b></p><p>More</p>"}"#,
+                Some("Hello world"),
+            ),
+            (
+                &*format!(r#"{{"id": "1", "content_text": "{long}"}}"#),
+                Some(&*truncated),
+            ),
+            (
+                r#"{"id": "1", "content_text": "", "url": "https://example.com/1"}"#,
+                Some("https://example.com/1"),
+            ),
+            (r#"{"id": "1"}"#, None),
+        ];
+
+        for (item, expected) in cases {
+            let feed: Feed =
+                serde_json::from_str(&format!(r#"{{"title": "Feed", "items": [{item}]}}"#))
+                    .unwrap();
+            assert_eq!(feed.items[0].title.as_deref(), expected, "{item}");
+        }
+    }
+}
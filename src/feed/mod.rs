@@ -1,4 +1,7 @@
+pub mod date;
 pub mod item;
+pub mod json;
+pub mod rdf;
 
 use crate::error::FeedError;
 
@@ -10,32 +13,60 @@ use tracing::debug;
 
 #[derive(Debug)]
 pub enum Feed {
-    Rss(rss::Channel),
-    Atom(atom_syndication::Feed),
+    Rss(Box<rss::Channel>),
+    Atom(Box<atom_syndication::Feed>),
+    Json(Box<json::Feed>),
+    Rdf(Box<rdf::Channel>),
 }
 
 impl<'a> Feed {
-    pub fn read_from<R>(reader: R) -> Result<Self, FeedError>
+    /// Reads a feed in any supported format.
+    ///
+    /// The format is detected by the `content_type` (if any) and the content
+    /// itself.
+    pub fn read_from<R>(mut reader: R, content_type: Option<&str>) -> Result<Self, FeedError>
+    where
+        R: BufRead + Copy,
+    {
+        let is_json = match content_type {
+            Some(v) if is_json_content_type(v) => true,
+            _ => starts_with_json_object(reader.fill_buf()?),
+        };
+
+        // The RSS parser only accepts RSS 1.0 documents with specific
+        // namespace prefixes and ignores the RDF specific elements.
+        let feed = if is_json {
+            Self::Json(Box::new(serde_json::from_reader(reader)?))
+        } else if rdf::is_rdf(reader)? {
+            Self::Rdf(Box::new(rdf::Channel::read_from(reader)?))
+        } else {
+            Self::read_xml_from(reader)?
+        };
+
+        debug!(
+            format = %feed.format(),
+            items = feed.item_count(),
+            "parsed feed"
+        );
+
+        Ok(feed)
+    }
+
+    fn read_xml_from<R>(reader: R) -> Result<Self, FeedError>
     where
         R: BufRead + Copy,
     {
         let feed = match rss::Channel::read_from(reader) {
-            Ok(channel) => Self::Rss(channel),
+            Ok(channel) => Self::Rss(Box::new(channel)),
             Err(e) => match e {
                 rss::Error::InvalidStartTag => {
                     let feed = atom_syndication::Feed::read_from(reader)?;
-                    Self::Atom(feed)
+                    Self::Atom(Box::new(feed))
                 }
                 _ => return Err(e)?,
             },
         };
 
-        debug!(
-            format = %if feed.is_rss() { "RSS" } else { "Atom" },
-            items = feed.items().len(),
-            "parsed feed"
-        );
-
         Ok(feed)
     }
 
@@ -43,6 +74,8 @@ impl<'a> Feed {
         match self {
             Feed::Rss(c) => &c.title,
             Feed::Atom(f) => &f.title.value,
+            Feed::Json(f) => &f.title,
+            Feed::Rdf(c) => &c.title,
         }
     }
 
@@ -54,11 +87,17 @@ impl<'a> Feed {
                 .iter()
                 .find(|s| s.rel() == "alternate")
                 .map(|s| s.href()),
+            Feed::Json(f) => f.home_page_url.as_deref(),
+            Feed::Rdf(c) => c.link.as_deref(),
         }
     }
 
-    pub fn items(&'a self) -> Vec<Item<'a>> {
+    /// Returns the items sorted by date, newest first.
+    ///
+    /// `name` is the configured name of the feed.
+    pub fn items(&'a self, name: &'a str) -> Vec<Item<'a>> {
         let source: Source<'a> = Source {
+            name,
             title: self.title(),
             url: self.link(),
         };
@@ -67,12 +106,38 @@ impl<'a> Feed {
             Feed::Rss(c) => c
                 .items()
                 .iter()
-                .map(|v| Item::Rss { source, item: v })
+                .map(|v| Item::Rss {
+                    source,
+                    item: v,
+                    fallback_date: None,
+                })
                 .collect(),
             Feed::Atom(f) => f
                 .entries()
                 .iter()
-                .map(|v| Item::Atom { source, entry: v })
+                .map(|v| Item::Atom {
+                    source,
+                    entry: v,
+                    fallback_date: None,
+                })
+                .collect(),
+            Feed::Json(f) => f
+                .items
+                .iter()
+                .map(|v| Item::Json {
+                    source,
+                    item: v,
+                    fallback_date: None,
+                })
+                .collect(),
+            Feed::Rdf(c) => c
+                .items
+                .iter()
+                .map(|v| Item::Rdf {
+                    source,
+                    item: v,
+                    fallback_date: None,
+                })
                 .collect(),
         };
 
@@ -81,19 +146,34 @@ impl<'a> Feed {
         items
     }
 
-    /// Returns `true` if the feed is [`Rss`].
-    ///
-    /// [`Rss`]: Feed::Rss
-    #[must_use]
-    pub fn is_rss(&self) -> bool {
-        matches!(self, Self::Rss(..))
+    fn item_count(&self) -> usize {
+        match self {
+            Feed::Rss(c) => c.items().len(),
+            Feed::Atom(f) => f.entries().len(),
+            Feed::Json(f) => f.items.len(),
+            Feed::Rdf(c) => c.items.len(),
+        }
     }
 
-    /// Returns `true` if the feed is [`Atom`].
-    ///
-    /// [`Atom`]: Feed::Atom
-    #[must_use]
-    pub fn is_atom(&self) -> bool {
-        matches!(self, Self::Atom(..))
+    /// Returns the name of the feed format
+    pub fn format(&self) -> &'static str {
+        match self {
+            Feed::Rss(_) => "RSS",
+            Feed::Atom(_) => "Atom",
+            Feed::Json(_) => "JSON",
+            Feed::Rdf(_) => "RDF",
+        }
     }
 }
+
+fn is_json_content_type(value: &str) -> bool {
+    let essence = value.split(';').next().unwrap_or_default().trim();
+    essence.eq_ignore_ascii_case("application/feed+json")
+        || essence.eq_ignore_ascii_case("application/json")
+}
+
+/// Sniffs whether the content looks like a JSON object
+fn starts_with_json_object(buf: &[u8]) -> bool {
+    let buf = buf.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(buf);
+    buf.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{')
+}
@@ -0,0 +1,259 @@
+//! [RSS 1.0](https://web.resource.org/rss/1.0/spec) (RDF Site Summary)
+//!
+//! Elements are matched by their local name, so namespace prefixes other
+//! than the common ones are accepted as well.
+
+use crate::error::FeedError;
+
+use std::io::BufRead;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Decoder, Reader,
+};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Channel {
+    pub title: String,
+    pub link: Option<String>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Item {
+    pub about: Option<String>,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    pub description: Option<String>,
+    /// `content:encoded`
+    pub content: Option<String>,
+    /// `dc:date`
+    pub date: Option<String>,
+    /// `dc:creator`
+    pub creators: Vec<String>,
+    /// `dc:subject`
+    pub subjects: Vec<String>,
+}
+
+impl Channel {
+    pub fn read_from<R: BufRead>(reader: R) -> Result<Self, FeedError> {
+        let mut reader = Reader::from_reader(reader);
+        reader.config_mut().trim_text(true);
+
+        let mut channel = Channel::default();
+        let mut item: Option<Item> = None;
+        let mut path: Vec<Vec<u8>> = Vec::new();
+        let mut text = String::new();
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) => {
+                    let name = e.local_name().as_ref().to_vec();
+
+                    if path.is_empty() && name != b"RDF" {
+                        return Err(FeedError::Rdf("root element is not rdf:RDF".to_string()));
+                    }
+
+                    if path.len() == 1 && name == b"item" {
+                        item = Some(Item {
+                            about: about_attribute(&e, reader.decoder())?,
+                            ..Default::default()
+                        });
+                    }
+
+                    path.push(name);
+                    text.clear();
+                }
+                Event::Text(e) => text.push_str(&e.unescape()?),
+                Event::CData(e) => {
+                    let value = reader
+                        .decoder()
+                        .decode(&e)
+                        .map_err(quick_xml::Error::from)?;
+                    text.push_str(&value);
+                }
+                Event::End(_) => {
+                    let Some(name) = path.pop() else {
+                        break;
+                    };
+                    let value = std::mem::take(&mut text);
+
+                    match (path.len(), path.last().map(|v| v.as_slice()), item.as_mut()) {
+                        (1, _, Some(_)) if name == b"item" => {
+                            channel.items.extend(item.take());
+                        }
+                        (2, Some(b"item"), Some(item)) => match name.as_slice() {
+                            b"title" => item.title = Some(value),
+                            b"link" => item.link = Some(value),
+                            b"description" => item.description = Some(value),
+                            b"encoded" => item.content = Some(value),
+                            b"date" => item.date = Some(value),
+                            b"creator" => item.creators.push(value),
+                            b"subject" => item.subjects.push(value),
+                            _ => {}
+                        },
+                        (2, Some(b"channel"), _) => match name.as_slice() {
+                            b"title" => channel.title = value,
+                            b"link" => channel.link = Some(value),
+                            _ => {}
+                        },
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+
+            buf.clear();
+        }
+
+        Ok(channel)
+    }
+}
+
+/// Returns `true` if the root element of the document is `rdf:RDF`
+pub fn is_rdf<R: BufRead>(reader: R) -> Result<bool, FeedError> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => return Ok(e.local_name().as_ref() == b"RDF"),
+            Event::Eof => return Ok(false),
+            _ => {}
+        }
+
+        buf.clear();
+    }
+}
+
+fn about_attribute(e: &BytesStart, decoder: Decoder) -> Result<Option<String>, FeedError> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(quick_xml::Error::from)?;
+        if attr.key.local_name().as_ref() == b"about" {
+            return Ok(Some(attr.decode_and_unescape_value(decoder)?.into_owned()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSS_1_0: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<rdf:RDF
+  xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+  xmlns:dc="http://purl.org/dc/elements/1.1/"
+  xmlns:content="http://purl.org/rss/1.0/modules/content/"
+  xmlns="http://purl.org/rss/1.0/">
+  <channel rdf:about="https://example.com/">
+    <title>Example &amp; Co</title>
+    <link>https://example.com/</link>
+    <description>Channel description</description>
+    <items>
+      <rdf:Seq>
+        <rdf:li rdf:resource="https://example.com/1"/>
+        <rdf:li rdf:resource="https://example.com/2"/>
+      </rdf:Seq>
+    </items>
+  </channel>
+  <item rdf:about="https://example.com/1">
+    <title>First</title>
+    <link>https://example.com/1</link>
+    <description><![CDATA[<p>Some <b>HTML</b></p>]]></description>
+    <content:encoded><![CDATA[<p>Full</p>]]></content:encoded>
+    <dc:date>2024-01-02T03:04:05+01:00</dc:date>
+    <dc:creator>Alice</dc:creator>
+    <dc:creator>Bob</dc:creator>
+    <dc:subject>News</dc:subject>
+    <dc:subject>Rust</dc:subject>
+  </item>
+  <item>
+    <title>Second</title>
+  </item>
+</rdf:RDF>"#;
+
+    #[test]
+    fn read_channels() {
+        let cases = [
+            (
+                RSS_1_0,
+                Ok(Channel {
+                    title: "Example & Co".to_string(),
+                    link: Some("https://example.com/".to_string()),
+                    items: vec![
+                        Item {
+                            about: Some("https://example.com/1".to_string()),
+                            title: Some("First".to_string()),
+                            link: Some("https://example.com/1".to_string()),
+                            description: Some("<p>Some <b>HTML</b></p>".to_string()),
+                            content: Some("<p>Full</p>".to_string()),
+                            date: Some("2024-01-02T03:04:05+01:00".to_string()),
+                            creators: vec!["Alice".to_string(), "Bob".to_string()],
+                            subjects: vec!["News".to_string(), "Rust".to_string()],
+                        },
+                        Item {
+                            title: Some("Second".to_string()),
+                            ..Default::default()
+                        },
+                    ],
+                }),
+            ),
+            // Other namespace prefixes are accepted
+            (
+                r#"<r:RDF xmlns:r="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+                    xmlns:x="http://purl.org/dc/elements/1.1/">
+                  <item r:about="a"><x:date>2024-01-01</x:date></item>
+                </r:RDF>"#,
+                Ok(Channel {
+                    items: vec![Item {
+                        about: Some("a".to_string()),
+                        date: Some("2024-01-01".to_string()),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            ),
+            // Only items directly under rdf:RDF are read
+            (
+                r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+                  <channel><title>Channel</title><item><title>Nested</title></item></channel>
+                </rdf:RDF>"#,
+                Ok(Channel {
+                    title: "Channel".to_string(),
+                    ..Default::default()
+                }),
+            ),
+            (
+                r#"<rss version="2.0"><channel><title>RSS</title></channel></rss>"#,
+                Err("rdf error: root element is not rdf:RDF".to_string()),
+            ),
+        ];
+
+        for (document, expected) in cases {
+            let result = Channel::read_from(document.as_bytes()).map_err(|e| e.to_string());
+            assert_eq!(result, expected, "{document}");
+        }
+    }
+
+    #[test]
+    fn detect_rdf() {
+        let cases = [
+            (RSS_1_0, true),
+            (
+                "<?xml version=\"1.0\"?>\n<!-- comment -->\n<RDF></RDF>",
+                true,
+            ),
+            ("<rss version=\"2.0\"></rss>", false),
+            ("<feed xmlns=\"http://www.w3.org/2005/Atom\"></feed>", false),
+            ("", false),
+        ];
+
+        for (document, expected) in cases {
+            assert_eq!(is_rdf(document.as_bytes()).unwrap(), expected, "{document}");
+        }
+    }
+}
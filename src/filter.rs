@@ -0,0 +1,239 @@
+use crate::feed::item::FeedItem;
+
+use std::fmt;
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// Decides which items are forwarded
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Filter {
+    /// How the include rules are combined
+    #[serde(default, rename = "match")]
+    pub mode: Match,
+    /// Items must match these rules to be forwarded
+    #[serde(default)]
+    pub include: Vec<Rule>,
+    /// Items matching any of these rules are not forwarded
+    #[serde(default)]
+    pub exclude: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Match {
+    /// All rules must match
+    #[default]
+    All,
+    /// At least one rule must match
+    Any,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub field: Field,
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+}
+
+impl PartialEq for Rule {
+    fn eq(&self, other: &Self) -> bool {
+        self.field == other.field && self.pattern.as_str() == other.pattern.as_str()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Title,
+    Description,
+    Content,
+    Link,
+    Author,
+    Category,
+}
+
+/// The reason why an item was filtered
+#[derive(Debug)]
+pub enum Rejection<'a> {
+    /// The item matched an exclude rule
+    Excluded(&'a Rule),
+    /// The item did not match an include rule
+    NotIncluded(&'a Rule),
+    /// The item matched none of the include rules
+    NoneIncluded,
+}
+
+impl Filter {
+    /// Returns the reason why the item is filtered, or `None` if the item
+    /// should be forwarded.
+    pub fn check<'a, I>(&self, item: &'a I) -> Option<Rejection<'_>>
+    where
+        I: FeedItem<'a>,
+    {
+        if let Some(rule) = self.exclude.iter().find(|r| r.matches(item)) {
+            return Some(Rejection::Excluded(rule));
+        }
+
+        if self.include.is_empty() {
+            return None;
+        }
+
+        match self.mode {
+            Match::All => self
+                .include
+                .iter()
+                .find(|r| !r.matches(item))
+                .map(Rejection::NotIncluded),
+            Match::Any if self.include.iter().any(|r| r.matches(item)) => None,
+            Match::Any => Some(Rejection::NoneIncluded),
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches<'a, I>(&self, item: &'a I) -> bool
+    where
+        I: FeedItem<'a>,
+    {
+        // Text fields are matched without markup, if it can be removed, and
+        // without the trailing line breaks left by the conversion
+        let text = |text: Result<Option<String>, html2text::Error>, raw: Option<&str>| {
+            text.ok()
+                .flatten()
+                .or_else(|| raw.map(|v| v.to_string()))
+                .is_some_and(|v| self.pattern.is_match(v.trim_end()))
+        };
+
+        match self.field {
+            Field::Title => text(item.title_as_text(), item.title()),
+            Field::Description => text(item.description_as_text(), item.description()),
+            Field::Content => text(item.content_as_text(), item.content()),
+            Field::Link => item.link().is_some_and(|v| self.pattern.is_match(v)),
+            Field::Author => item.authors().iter().any(|v| self.pattern.is_match(v.name)),
+            Field::Category => item.categories().iter().any(|v| self.pattern.is_match(v)),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Title => "title",
+            Field::Description => "description",
+            Field::Content => "content",
+            Field::Link => "link",
+            Field::Author => "author",
+            Field::Category => "category",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} =~ /{}/", self.field, self.pattern)
+    }
+}
+
+impl fmt::Display for Rejection<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Excluded(rule) => write!(f, "exclude {rule}"),
+            Rejection::NotIncluded(rule) => write!(f, "include {rule}"),
+            Rejection::NoneIncluded => f.write_str("no include rule matched"),
+        }
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item() -> rss::Item {
+        let mut item = rss::Item::default();
+        item.set_title("Release of <version> 1.0".to_string());
+        item.set_description("<p><b>Rust</b> &amp; friends</p>".to_string());
+        item.set_link("https://example.com/news/1".to_string());
+        item.set_author("Alice".to_string());
+        item.set_categories(vec!["rust".into(), "release".into()]);
+        item
+    }
+
+    #[test]
+    fn check_items() {
+        let cases = [
+            ("", None),
+            // All include rules must match by default
+            (
+                r#"include = [{ field = "title", pattern = "Release" }, { field = "author", pattern = "^Alice$" }]"#,
+                None,
+            ),
+            (
+                r#"include = [{ field = "title", pattern = "Release" }, { field = "author", pattern = "Bob" }]"#,
+                Some("include author =~ /Bob/"),
+            ),
+            // One include rule is enough with `any`
+            (
+                r#"match = "any"
+                include = [{ field = "title", pattern = "Beta" }, { field = "category", pattern = "^rust$" }]"#,
+                None,
+            ),
+            (
+                r#"match = "any"
+                include = [{ field = "title", pattern = "Beta" }, { field = "link", pattern = "/blog/" }]"#,
+                Some("no include rule matched"),
+            ),
+            // Exclude rules win over include rules
+            (
+                r#"include = [{ field = "title", pattern = "Release" }]
+                exclude = [{ field = "category", pattern = "^release$" }]"#,
+                Some("exclude category =~ /^release$/"),
+            ),
+            (
+                r#"exclude = [{ field = "link", pattern = "/blog/" }, { field = "author", pattern = "Bob" }]"#,
+                None,
+            ),
+            // Markup is removed before matching
+            (
+                r#"include = [{ field = "description", pattern = "^Rust & friends$" }]"#,
+                None,
+            ),
+            (
+                r#"exclude = [{ field = "description", pattern = "<b>" }]"#,
+                None,
+            ),
+            (
+                r#"include = [{ field = "title", pattern = "^Release of 1.0$" }]"#,
+                None,
+            ),
+            // Missing fields never match
+            (
+                r#"include = [{ field = "content", pattern = "" }]"#,
+                Some("include content =~ //"),
+            ),
+        ];
+
+        let item = item();
+
+        for (filter, expected) in cases {
+            let filter: Filter = toml::from_str(filter).unwrap();
+            assert_eq!(
+                filter.check(&item).map(|v| v.to_string()).as_deref(),
+                expected,
+                "{filter:?}"
+            );
+        }
+    }
+}
@@ -1,19 +1,25 @@
 mod config;
 mod error;
 mod feed;
+mod filter;
+mod retry;
+mod runtime;
 mod sink;
+mod state;
+mod supervisor;
+mod template;
 mod watcher;
 
 use crate::{
-    config::{Config, Feed},
-    watcher::Watcher,
+    config::Config,
+    runtime::Runtime,
+    supervisor::{Health, Supervisor},
 };
 
 use std::{
-    collections::HashMap,
     env,
     io::{stdout, IsTerminal},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process,
     str::FromStr,
     time::Duration,
@@ -27,10 +33,10 @@ use reqwest::{
 };
 use tokio::{
     signal::unix::{signal, SignalKind},
-    sync::broadcast,
-    task::JoinSet,
+    sync::{mpsc, watch},
+    time,
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 #[cfg(feature = "mimalloc")]
@@ -41,11 +47,23 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 struct Args {
+    command: Command,
     config: PathBuf,
     format: LogFormat,
     color: AnsiOutput,
     debug: bool,
     verbose: bool,
+    watch: bool,
+}
+
+#[derive(Debug)]
+enum Command {
+    /// Runs the watchers of the config
+    Run,
+    /// Validates the config without running anything
+    Check,
+    /// Checks the feed once and prints what would be sent to its sinks
+    Preview { feed: String },
 }
 
 #[derive(Debug, Default)]
@@ -146,7 +164,7 @@ async fn main() -> Result<()> {
         LogFormat::Json => subscriber.json().init(),
     };
 
-    let config = match Config::from_file(args.config).await {
+    let config = match Config::from_file(&args.config).await {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Error while reading config: {e}");
@@ -156,17 +174,67 @@ async fn main() -> Result<()> {
 
     let client = build_client()?;
 
-    let mut tasks = watch_feeds(config.feeds, client)?;
-    let mut task_failed = false;
-    while let Some(res) = tasks.join_next().await {
-        let abort = if let Ok(r) = res { r.is_err() } else { true };
-        if abort && !task_failed {
-            tasks.abort_all();
-            task_failed = true;
+    match &args.command {
+        Command::Run => {}
+        Command::Check => {
+            println!(
+                "Config is valid: {} feed(s), {} shared sink(s)",
+                config.feeds.len(),
+                config.sinks.len()
+            );
+            return Ok(());
+        }
+        Command::Preview { feed } => {
+            let runtime = Runtime::read_only(client, config.state.clone());
+            if let Err(e) = runtime.preview(&config, feed).await {
+                eprintln!("Error while previewing feed: {e}");
+                process::exit(1);
+            }
+            return Ok(());
+        }
+    }
+
+    let (sources, sources_rx) = watch::channel(config.sources.clone());
+
+    let mut supervisor = Supervisor::new();
+    let mut runtime = Runtime::new(client, config.state.clone()).await?;
+
+    runtime.apply(&mut supervisor, config).await?;
+
+    let mut sig_int = signal(SignalKind::interrupt())?;
+    let mut sig_term = signal(SignalKind::terminate())?;
+    let mut sig_hup = signal(SignalKind::hangup())?;
+    let mut sig_usr1 = signal(SignalKind::user_defined1())?;
+
+    let mut config_changes = match args.watch {
+        true => Some(watch_config(sources_rx)),
+        false => None,
+    };
+
+    loop {
+        tokio::select! {
+            _ = supervisor.failed() => {
+                supervisor.abort().await;
+                eprintln!("Terminate due to a faulty watcher");
+                process::exit(1);
+            },
+            _ = sig_int.recv() => break,
+            _ = sig_term.recv() => break,
+            _ = sig_hup.recv() => {
+                info!("received hangup signal, reloading config");
+                reload(&args.config, &sources, &mut runtime, &mut supervisor).await;
+            },
+            Some(_) = async { config_changes.as_mut()?.recv().await } => {
+                info!("config file changed, reloading config");
+                reload(&args.config, &sources, &mut runtime, &mut supervisor).await;
+            },
+            _ = sig_usr1.recv() => log_health(&supervisor.health()),
         }
     }
 
-    if task_failed {
+    debug!("received termination signal");
+
+    if supervisor.shutdown().await.is_err() {
         eprintln!("Terminate due to a faulty watcher");
         process::exit(1);
     }
@@ -174,13 +242,87 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Applies the config file. An invalid config is rejected and the current
+/// one keeps running.
+async fn reload(
+    path: &Path,
+    sources: &watch::Sender<Vec<PathBuf>>,
+    runtime: &mut Runtime,
+    supervisor: &mut Supervisor,
+) {
+    let config = match Config::from_file(path).await {
+        Ok(c) => c,
+        Err(e) => {
+            error!(error = %e, "rejecting invalid config");
+            return;
+        }
+    };
+
+    sources.send_replace(config.sources.clone());
+
+    match runtime.apply(supervisor, config).await {
+        Ok(()) => info!("config reloaded"),
+        Err(e) => error!(error = %e, "error while applying config"),
+    }
+}
+
+fn log_health(health: &Health) {
+    let degraded = health.degraded();
+    if degraded.is_empty() {
+        info!("all feeds are running");
+    }
+    for (feed, status) in degraded {
+        warn!(%feed, %status, "feed is degraded");
+    }
+}
+
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Polls the modification times of the files and directories the config
+/// was read from
+fn watch_config(mut sources: watch::Receiver<Vec<PathBuf>>) -> mpsc::Receiver<()> {
+    let (tx, rx) = mpsc::channel(1);
+
+    tokio::spawn(async move {
+        let modified = |paths: &[PathBuf]| {
+            paths
+                .iter()
+                .map(|v| std::fs::metadata(v).and_then(|v| v.modified()).ok())
+                .collect::<Vec<_>>()
+        };
+
+        let mut last = modified(&sources.borrow_and_update());
+        let mut interval = time::interval(CONFIG_POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            // The sources changed due to a reload
+            if sources.has_changed().unwrap_or(false) {
+                last = modified(&sources.borrow_and_update());
+                continue;
+            }
+
+            let current = modified(&sources.borrow());
+            if current != last {
+                last = current;
+                if tx.send(()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    rx
+}
+
 const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 
 const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 const DEFAULT_HEADERS: &[(HeaderName, &str)] = &[(
     header::ACCEPT,
-    "application/atom+xml, application/rss+xml, application/xml, text/xml",
+    "application/atom+xml, application/rss+xml, application/feed+json, application/xml, text/xml, application/json",
 )];
 
 fn build_client() -> Result<Client> {
@@ -204,12 +346,20 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 const DESCRIPTION: &str = env!("CARGO_PKG_DESCRIPTION");
 
+const COMMANDS: &str = "\
+    COMMANDS:
+      check                  Validates the config without network access and exits
+      preview                Checks the feed once and prints the payloads of its sinks
+                             to stdout instead of sending them
+";
+
 const OPTIONS: &str = "\
     OPTIONS:
       -f, --format <FORMAT>  Log format: full, pretty, compact, json (default: full)
       --color <WHEN>         Colorize output: auto, always, never (default: auto)
       --debug                Enables debug mode
       --verbose              Enables verbose mode
+      --watch                Reloads the config when a config file changes
       -h, --help             Show this help message
       -v, --version          Show version information
 ";
@@ -220,8 +370,11 @@ fn print_help() {
 {NAME} v{VERSION} by {AUTHORS}
 {DESCRIPTION}
 
-    USAGE: {NAME} [OPTIONS] <CONFIG_FILE>
+    USAGE: {NAME} [OPTIONS] <CONFIG>
+           {NAME} check [OPTIONS] <CONFIG>
+           {NAME} preview [OPTIONS] <CONFIG> <FEED>
 
+    {COMMANDS}
     {OPTIONS}
 ",
     );
@@ -240,14 +393,34 @@ fn parse_args() -> Result<Args> {
         process::exit(0);
     }
 
+    let debug = pargs.contains("--debug");
+    let verbose = pargs.contains("--verbose");
+    let watch = pargs.contains("--watch");
+    let format = pargs
+        .opt_value_from_str(["-f", "--format"])?
+        .unwrap_or_default();
+    let color = pargs.opt_value_from_str("--color")?.unwrap_or_default();
+
+    // Without a subcommand, the first free argument is the config
+    let (command, config) = match pargs.subcommand()?.as_deref() {
+        Some("check") => (Command::Check, pargs.free_from_str()?),
+        Some("preview") => {
+            let config = pargs.free_from_str()?;
+            let feed = pargs.free_from_str()?;
+            (Command::Preview { feed }, config)
+        }
+        Some(v) => (Command::Run, PathBuf::from(v)),
+        None => (Command::Run, pargs.free_from_str()?),
+    };
+
     let args = Args {
-        debug: pargs.contains("--debug"),
-        verbose: pargs.contains("--verbose"),
-        format: pargs
-            .opt_value_from_str(["-f", "--format"])?
-            .unwrap_or_default(),
-        color: pargs.opt_value_from_str("--color")?.unwrap_or_default(),
-        config: pargs.free_from_str()?,
+        command,
+        config,
+        format,
+        color,
+        debug,
+        verbose,
+        watch,
     };
 
     pargs.finish();
@@ -272,53 +445,3 @@ fn parse_env_filter(debug: bool, verbose: bool) -> EnvFilter {
         (false, _, _) => EnvFilter::from_default_env(),
     }
 }
-
-fn watch_feeds(feeds: HashMap<String, Feed>, client: Client) -> Result<JoinSet<Result<()>>> {
-    let mut tasks = JoinSet::new();
-
-    let (tx, _) = broadcast::channel(feeds.len());
-
-    for (name, config) in feeds.into_iter() {
-        let sink = config.sink.sink(&client)?;
-        let watcher = Watcher::new(
-            config.url,
-            sink,
-            config.interval,
-            client.clone(),
-            config.retry_limit,
-        )?;
-
-        let rx = tx.subscribe();
-
-        tasks.spawn(async move {
-            info!("starting watcher for \"{name}\"");
-
-            if let Err(err) = watcher.watch(rx).await {
-                error!(
-                    feed = %name,
-                    error = %err,
-                    "shutting down watcher due to an error",
-                );
-                return Err(err);
-            }
-
-            Ok(())
-        });
-    }
-
-    tokio::spawn(async move {
-        let mut sig_int = signal(SignalKind::interrupt()).unwrap();
-        let mut sig_term = signal(SignalKind::terminate()).unwrap();
-
-        tokio::select! {
-            _ = sig_int.recv() => {},
-            _ = sig_term.recv() => {},
-        };
-
-        debug!("received termination signal");
-
-        tx.send(()).unwrap();
-    });
-
-    Ok(tasks)
-}
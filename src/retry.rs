@@ -0,0 +1,257 @@
+use crate::{error::Error, Result};
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{header, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use tokio::time;
+use tracing::warn;
+
+/// How often a rate limited request is repeated before giving up
+const RATE_LIMIT_RETRIES: usize = 3;
+
+/// Longer delays are left to the retry policy of the watcher
+const MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+
+/// Decides when failed checks of a feed are retried
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Delay before the first retry
+    #[serde(default = "initial_delay_default", with = "humantime_serde")]
+    pub initial_delay: Duration,
+    /// Factor by which the delay grows with each retry
+    #[serde(default = "multiplier_default")]
+    pub multiplier: f64,
+    /// Upper bound of the delay
+    #[serde(default = "max_delay_default", with = "humantime_serde")]
+    pub max_delay: Duration,
+    /// Fraction of the delay that is randomly added or subtracted
+    #[serde(default = "jitter_default")]
+    pub jitter: f64,
+    /// Maximum number of attempts, including the first one.
+    ///
+    /// Defaults to `retry_limit` + 1 of the feed.
+    pub max_attempts: Option<usize>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: initial_delay_default(),
+            multiplier: multiplier_default(),
+            max_delay: max_delay_default(),
+            jitter: jitter_default(),
+            max_attempts: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.multiplier.is_nan() || self.multiplier < 1.0 {
+            return Err("multiplier must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err("jitter must be between 0 and 1".to_string());
+        }
+        if self.max_attempts == Some(0) {
+            return Err("max_attempts must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the delay before the given retry, starting at 1
+    pub fn delay(&self, retry: usize) -> Duration {
+        let exp = i32::try_from(retry.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exp))
+            .min(self.max_delay.as_secs_f64());
+        let jitter = delay * self.jitter * (fastrand::f64() * 2.0 - 1.0);
+
+        Duration::try_from_secs_f64(delay + jitter).unwrap_or(self.max_delay)
+    }
+}
+
+/// Returns `true` if the operation that caused the error might succeed later
+pub fn is_retriable(err: &Error) -> bool {
+    match err {
+        Error::Request(e) if e.is_timeout() || e.is_connect() => true,
+        Error::Request(e) if e.is_status() => {
+            let status = e.status().unwrap();
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        Error::RateLimited { .. } => true,
+        Error::Smtp(e) => e.is_transient() || e.is_timeout() || is_connection_error(e),
+        _ => false,
+    }
+}
+
+/// Returns `true` if the SMTP error was caused by an I/O error, e.g. a
+/// refused or reset connection
+fn is_connection_error(err: &lettre::transport::smtp::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+
+    while let Some(err) = source {
+        if err.is::<std::io::Error>() {
+            return true;
+        }
+        source = err.source();
+    }
+
+    false
+}
+
+/// Returns the time the server asked us to wait before retrying
+pub fn retry_after(err: &Error) -> Option<Duration> {
+    match err {
+        Error::RateLimited { retry_after, .. } => *retry_after,
+        _ => None,
+    }
+}
+
+/// Sends the request and repeats it while the server responds with
+/// `429 Too Many Requests` and asks for a short wait.
+pub async fn send(req: RequestBuilder) -> Result<Response> {
+    let mut retries = 0;
+
+    loop {
+        let res = req
+            .try_clone()
+            .expect("request body should not be a stream")
+            .send()
+            .await?;
+
+        match error_for_status(res).await {
+            // The URL is not logged, it might contain a token
+            Err(Error::RateLimited {
+                retry_after: Some(delay),
+                ..
+            }) if retries < RATE_LIMIT_RETRIES && delay <= MAX_RATE_LIMIT_DELAY => {
+                retries += 1;
+                warn!(?delay, "rate limited, waiting before retrying");
+                time::sleep(delay).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Like [`Response::error_for_status`], but turns `429 Too Many Requests`
+/// into [`Error::RateLimited`] with the advertised delay.
+///
+/// The delay is taken from the `Retry-After` header, Discord's
+/// `X-RateLimit-Reset-After` header or a `retry_after` field in the JSON
+/// body or its `parameters` (Telegram), in that order. Matrix' `retry_after_ms`
+/// field is given in milliseconds.
+pub async fn error_for_status(res: Response) -> Result<Response> {
+    if res.status() != StatusCode::TOO_MANY_REQUESTS {
+        return Ok(res.error_for_status()?);
+    }
+
+    let host = res.url().host_str().unwrap_or_default().to_string();
+    let header_value = |name: &str| res.headers().get(name).and_then(|v| v.to_str().ok());
+
+    let mut retry_after = header_value(header::RETRY_AFTER.as_str())
+        .and_then(parse_retry_after)
+        .or_else(|| header_value("x-ratelimit-reset-after").and_then(parse_seconds));
+
+    if retry_after.is_none() {
+        #[derive(Deserialize)]
+        struct Body {
+            retry_after: Option<f64>,
+            retry_after_ms: Option<u64>,
+            parameters: Option<Parameters>,
+        }
+
+        /// Telegram's response parameters
+        #[derive(Deserialize)]
+        struct Parameters {
+            retry_after: Option<f64>,
+        }
+
+        retry_after = res
+            .json::<Body>()
+            .await
+            .ok()
+            .and_then(|v| match v.retry_after_ms {
+                Some(ms) => Some(Duration::from_millis(ms)),
+                None => v
+                    .retry_after
+                    .or(v.parameters?.retry_after)
+                    .and_then(|v| Duration::try_from_secs_f64(v).ok()),
+            });
+    }
+
+    Err(Error::RateLimited { host, retry_after })
+}
+
+/// Parses either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    parse_seconds(value).or_else(|| {
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        Some(
+            (date.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or_default(),
+        )
+    })
+}
+
+fn parse_seconds(value: &str) -> Option<Duration> {
+    Duration::try_from_secs_f64(value.trim().parse().ok()?).ok()
+}
+
+const fn initial_delay_default() -> Duration {
+    Duration::from_secs(1)
+}
+
+const fn multiplier_default() -> f64 {
+    2.0
+}
+
+const fn max_delay_default() -> Duration {
+    Duration::from_secs(300)
+}
+
+const fn jitter_default() -> f64 {
+    0.2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_retry_after_values() {
+        let cases = [
+            ("0", Some(Duration::ZERO)),
+            ("120", Some(Duration::from_secs(120))),
+            (" 1.5\t", Some(Duration::from_millis(1500))),
+            ("-1", None),
+            ("NaN", None),
+            ("inf", None),
+            ("", None),
+            ("soon", None),
+            // Dates in the past do not delay
+            ("Wed, 21 Oct 2015 07:28:00 GMT", Some(Duration::ZERO)),
+            ("21 Oct 2015 07:28:00 +0000", Some(Duration::ZERO)),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(parse_retry_after(value), expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn parse_retry_after_future_date() {
+        let value = (Utc::now() + chrono::Duration::seconds(60)).to_rfc2822();
+        let delay = parse_retry_after(&value).unwrap();
+
+        assert!(
+            delay > Duration::from_secs(55) && delay <= Duration::from_secs(60),
+            "{delay:?}"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{
+    config::{Config, Feed, SinkRef},
+    error::Error,
+    sink::{preview::Preview, rate_limit::RateLimiters, AnySink, Sink, SinkOptions},
+    state::{AnyStore, State, StoreOptions, Validators},
+    supervisor::Supervisor,
+    watcher::Watcher,
+    Result,
+};
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Weak},
+};
+
+use reqwest::Client;
+use tracing::{error, info, warn};
+
+/// The running feeds and everything needed to start or restart them when
+/// the config changes
+#[derive(Debug)]
+pub struct Runtime {
+    client: Client,
+    limiters: RateLimiters,
+    store_options: Option<StoreOptions>,
+    store: Option<Arc<AnyStore>>,
+    feeds: HashMap<String, Feed>,
+    /// Named sinks in use. The sinks are owned by the watchers, so that the
+    /// last watcher using a sink shuts it down.
+    sinks: HashMap<String, (SinkOptions, Weak<AnySink>)>,
+}
+
+impl Runtime {
+    pub async fn new(client: Client, store_options: Option<StoreOptions>) -> Result<Self> {
+        let store = match store_options.clone() {
+            Some(opts) => Some(Arc::new(opts.store().await?)),
+            None => None,
+        };
+
+        Ok(Self::with_store(client, store_options, store))
+    }
+
+    /// Creates a runtime that only reads the state, e.g. for previews. The
+    /// state store is not created if it does not exist.
+    pub fn read_only(client: Client, store_options: Option<StoreOptions>) -> Self {
+        let store = store_options
+            .clone()
+            .map(|opts| Arc::new(opts.read_only_store()));
+
+        Self::with_store(client, store_options, store)
+    }
+
+    fn with_store(
+        client: Client,
+        store_options: Option<StoreOptions>,
+        store: Option<Arc<AnyStore>>,
+    ) -> Self {
+        Self {
+            client,
+            limiters: RateLimiters::default(),
+            store_options,
+            store,
+            feeds: HashMap::new(),
+            sinks: HashMap::new(),
+        }
+    }
+
+    /// Starts the watchers of new feeds, stops the watchers of removed feeds
+    /// and restarts the watchers of changed feeds.
+    ///
+    /// If a sink or watcher of the new config cannot be created, no watcher
+    /// is touched.
+    pub async fn apply(&mut self, supervisor: &mut Supervisor, config: Config) -> Result<()> {
+        if config.state != self.store_options {
+            warn!("changes of the state store take effect after a restart");
+        }
+
+        self.limiters.reload();
+
+        let referenced = config.referenced_sinks();
+
+        let mut shared_sinks = HashMap::new();
+        let mut changed_sinks = HashSet::new();
+        for (name, opts) in config.sinks.iter() {
+            if !referenced.contains(name) {
+                continue;
+            }
+
+            let current = self
+                .sinks
+                .get(name)
+                .filter(|(current, _)| current == opts)
+                .and_then(|(_, sink)| sink.upgrade());
+
+            let sink = match current {
+                Some(sink) => sink,
+                None => {
+                    changed_sinks.insert(name.as_str());
+                    Arc::new(opts.clone().sink(&self.client, &self.limiters)?)
+                }
+            };
+
+            shared_sinks.insert(name.clone(), sink);
+        }
+
+        let mut watchers = Vec::new();
+        for (name, feed) in config.feeds.iter() {
+            let uses_changed_sink = feed.sinks().any(|v| match v {
+                SinkRef::Named(sink) => changed_sinks.contains(sink.as_str()),
+                SinkRef::Inline(_) => false,
+            });
+
+            if self.feeds.get(name) == Some(feed) && !uses_changed_sink {
+                continue;
+            }
+
+            let factory = self.factory(name, feed, &shared_sinks);
+            watchers.push((name, factory.build()?, factory));
+        }
+
+        // The new config is valid, the watchers can be replaced now
+        let removed: Vec<String> = self
+            .feeds
+            .keys()
+            .filter(|v| !config.feeds.contains_key(*v))
+            .cloned()
+            .collect();
+
+        for name in removed.iter() {
+            info!("stopping watcher for \"{name}\"");
+            self.stop(supervisor, name).await;
+        }
+
+        let mut result = Ok(());
+        for (name, mut watcher, factory) in watchers {
+            let feed = &config.feeds[name];
+
+            let mut previous = None;
+            if let Some(current) = self.feeds.get(name) {
+                info!("restarting watcher for \"{name}\" due to config changes");
+                let url = current.url.clone();
+                previous = self.stop(supervisor, name).await;
+
+                // The cache validators only apply to the same URL
+                if let Some(state) = previous.as_mut().filter(|_| url != feed.url) {
+                    state.validators = Validators::default();
+                }
+            }
+
+            // The state is loaded after the previous watcher has saved it
+            if let Some(store) = &self.store {
+                watcher = match watcher.with_store(store.clone(), feed.backfill).await {
+                    Ok(v) => v,
+                    Err(err) => {
+                        error!(feed = %name, error = %err, "error while loading state");
+                        result = result.and(Err(err));
+                        continue;
+                    }
+                };
+            }
+
+            // The previous watcher continues where it left off, regardless
+            // of the store and backfill
+            if let Some(state) = previous {
+                watcher = watcher.with_state(state);
+            }
+
+            supervisor.spawn(watcher, feed.on_failure, move || factory.build());
+            self.feeds.insert(name.clone(), feed.clone());
+        }
+
+        self.sinks = shared_sinks
+            .iter()
+            .map(|(name, sink)| {
+                let opts = config.sinks[name].clone();
+                (name.clone(), (opts, Arc::downgrade(sink)))
+            })
+            .collect();
+
+        result
+    }
+
+    /// Stops the watcher of the feed and returns its state, unless it failed
+    async fn stop(&mut self, supervisor: &mut Supervisor, name: &str) -> Option<State> {
+        self.feeds.remove(name);
+
+        supervisor.stop(name).await.unwrap_or_else(|err| {
+            error!(feed = %name, error = %err, "error while stopping watcher");
+            None
+        })
+    }
+
+    fn factory(
+        &self,
+        name: &str,
+        feed: &Feed,
+        shared_sinks: &HashMap<String, Arc<AnySink>>,
+    ) -> WatcherFactory {
+        WatcherFactory {
+            name: name.to_string(),
+            feed: feed.clone(),
+            client: self.client.clone(),
+            limiters: self.limiters.clone(),
+            shared_sinks: shared_sinks
+                .iter()
+                .map(|(name, sink)| (name.clone(), Arc::downgrade(sink)))
+                .collect(),
+        }
+    }
+
+    /// Checks the feed once and writes the payloads its sinks would send to
+    /// stdout instead of sending them. The state is left unchanged.
+    pub async fn preview(&self, config: &Config, name: &str) -> Result<()> {
+        let feed = config
+            .feeds
+            .get(name)
+            .ok_or_else(|| Error::Config(format!("unknown feed \"{name}\"")))?;
+
+        let sinks = feed
+            .sinks()
+            .map(|sink| match sink {
+                SinkRef::Named(name) => {
+                    let id = format!("sinks.{name}");
+                    (id.clone(), Preview::new(id, config.sinks[name].clone()))
+                }
+                SinkRef::Inline(sink) => (
+                    sink.id.clone(),
+                    Preview::new(sink.id.clone(), sink.options.clone()),
+                ),
+            })
+            .collect();
+
+        let mut watcher = configure(&self.client, name, feed, sinks)?;
+        if let Some(store) = &self.store {
+            watcher = watcher.with_store(store.clone(), true).await?;
+        }
+
+        watcher.check_once().await
+    }
+}
+
+/// Creates the watcher of a feed together with its sinks, initially and
+/// whenever the watcher is restarted after a failure
+#[derive(Debug)]
+struct WatcherFactory {
+    name: String,
+    feed: Feed,
+    client: Client,
+    limiters: RateLimiters,
+    /// Shared sinks are not created again. They are owned by the watchers,
+    /// so that the last watcher using a sink shuts it down.
+    shared_sinks: HashMap<String, Weak<AnySink>>,
+}
+
+impl WatcherFactory {
+    fn build(&self) -> Result<Watcher<Arc<AnySink>>> {
+        let sinks = self
+            .feed
+            .sinks()
+            .map(|sink| match sink {
+                SinkRef::Named(name) => {
+                    let sink = self.shared_sinks[name].upgrade().ok_or_else(|| {
+                        Error::Sink(format!("shared sink \"{name}\" has been shut down"))
+                    })?;
+                    Ok((format!("sinks.{name}"), sink))
+                }
+                SinkRef::Inline(sink) => Ok((
+                    sink.id.clone(),
+                    Arc::new(sink.options.clone().sink(&self.client, &self.limiters)?),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        configure(&self.client, &self.name, &self.feed, sinks)
+    }
+}
+
+fn configure<T: Sink>(
+    client: &Client,
+    name: &str,
+    feed: &Feed,
+    sinks: Vec<(String, T)>,
+) -> Result<Watcher<T>> {
+    let mut watcher = Watcher::new(
+        name.to_string(),
+        feed.url.as_str(),
+        sinks,
+        feed.interval,
+        client.clone(),
+        feed.retry_limit,
+    )?
+    .with_detection(feed.detection)
+    .with_undated(feed.undated)
+    .with_retry(feed.retry.clone())
+    .with_headers(feed.header_map()?);
+
+    if let Some(filter) = &feed.filter {
+        watcher = watcher.with_filter(filter.clone());
+    }
+
+    Ok(watcher)
+}
@@ -6,7 +6,7 @@ use crate::{
 
 use super::Sink;
 
-use std::process::Stdio;
+use std::{env, os::unix::fs::PermissionsExt, path::Path, process::Stdio};
 
 use async_trait::async_trait;
 use chrono::{DateTime, FixedOffset};
@@ -73,6 +73,36 @@ impl Custom {
     }
 }
 
+/// Returns the NDJSON lines that would be written for the items
+pub fn render<'a, T>(items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    items
+        .iter()
+        .map(|item| {
+            let obj = Object::try_from_item(item)?;
+            Ok(serde_json::to_string(&obj)?)
+        })
+        .collect()
+}
+
+/// Returns `true` if the command is an executable file, either as a path or
+/// found in `PATH`
+pub fn command_exists(cmd: &str) -> bool {
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|v| v.is_file() && v.permissions().mode() & 0o111 != 0)
+    };
+
+    if cmd.contains('/') {
+        return is_executable(Path::new(cmd));
+    }
+
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| is_executable(&dir.join(cmd))))
+}
+
 #[async_trait]
 impl Sink for Custom {
     #[tracing::instrument(
@@ -91,10 +121,9 @@ impl Sink for Custom {
     {
         debug!(count = items.len(), "pushing items");
 
-        for item in items {
-            let obj = Object::try_from_item(item)?;
-            let mut json = serde_json::to_vec(&obj)?;
-            json.extend_from_slice(b"\n");
+        for line in render(items)? {
+            let mut json = line.into_bytes();
+            json.push(b'\n');
 
             if self.data_tx.send(json).await.is_err() {
                 return Err(Error::Sink("broken stdin task".to_string()));
@@ -127,13 +156,15 @@ impl Sink for Custom {
     }
 }
 
+/// JSON object of an item
 #[derive(Debug, Clone, Serialize)]
-struct Object<'a> {
+pub struct Object<'a> {
+    feed: Option<&'a str>,
     title: &'a str,
     description: Option<&'a str>,
     content: Option<&'a str>,
     link: &'a str,
-    date: DateTime<FixedOffset>,
+    date: Option<DateTime<FixedOffset>>,
     authors: Vec<Author<'a>>,
 }
 
@@ -145,6 +176,7 @@ where
 
     fn try_from_item(value: &'a T) -> std::result::Result<Self, Self::Error> {
         let obj = Self {
+            feed: value.source().map(|v| v.name),
             title: value
                 .title()
                 .ok_or_else(|| FeedError::Item("title is missing".to_string()))?,
@@ -1,15 +1,22 @@
 use crate::{
     error::FeedError,
     feed::item::{FeedItem, TryFromItem},
+    retry,
+    template::{Context, Template},
     Result,
 };
 
-use super::Sink;
+use super::{
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, Sink,
+};
+
+use std::{borrow::Cow, sync::Arc};
 
 use async_trait::async_trait;
 use chrono::{DateTime, FixedOffset};
 use reqwest::{Client, IntoUrl, Url};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 const PROVIDER: EmbedProvider<'static> = EmbedProvider {
@@ -17,16 +24,76 @@ const PROVIDER: EmbedProvider<'static> = EmbedProvider {
     url: Some(env!("CARGO_PKG_REPOSITORY")),
 };
 
+/// Templates for the text fields of the embed
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Templates {
+    title: Option<Template>,
+    description: Option<Template>,
+    author: Option<Template>,
+    footer: Option<Template>,
+}
+
+impl Templates {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.description.is_none()
+            && self.author.is_none()
+            && self.footer.is_none()
+    }
+
+    /// Renders the embed of the item
+    fn embed<'a, T>(&self, item: &'a T) -> Result<EmbedObject<'a>>
+    where
+        T: FeedItem<'a>,
+    {
+        let mut embed = EmbedObject::try_from_item(item)?;
+
+        if self.is_empty() {
+            return Ok(embed);
+        }
+
+        let ctx = Context::try_from_item(item)?;
+
+        if let Some(t) = &self.title {
+            embed.title = t.render(&ctx)?;
+        }
+        if let Some(t) = &self.description {
+            embed.description = t.render(&ctx)?;
+        }
+        if let Some(t) = &self.author {
+            embed.author.name = t.render(&ctx)?.into();
+        }
+        if let Some(t) = &self.footer {
+            embed.footer.text = t.render(&ctx)?.into();
+        }
+
+        Ok(embed)
+    }
+}
+
+/// Rate limit of a webhook, if not configured otherwise
+pub const DEFAULT_QUOTA: Quota = Quota::per_minute(30);
+
 #[derive(Debug)]
 pub struct Discord {
     url: Url,
+    template: Templates,
+    limiter: Arc<RateLimiter>,
     client: Client,
 }
 
 impl Discord {
-    pub fn new<T: IntoUrl>(url: T, client: Client) -> Result<Self> {
+    pub fn new<T: IntoUrl>(
+        url: T,
+        template: Templates,
+        limiter: Arc<RateLimiter>,
+        client: Client,
+    ) -> Result<Self> {
         Ok(Self {
             url: url.into_url()?,
+            template,
+            limiter,
             client,
         })
     }
@@ -46,30 +113,11 @@ impl Sink for Discord {
     where
         T: FeedItem<'a>,
     {
-        let length = items.len();
-        let limit = 10_usize;
-        let chunk_count = (length as f64 / limit as f64).ceil() as usize;
-
-        debug!(count = length, chunks = chunk_count, "pushing items");
-
-        let mut chunks: Vec<Body> = Vec::with_capacity(chunk_count);
-        for i in 0..chunk_count {
-            let pos = i * limit;
-            let chunk = items[pos..(pos + limit).min(length)]
-                .iter()
-                .map(EmbedObject::try_from_item)
-                .collect::<std::result::Result<Vec<EmbedObject>, FeedError>>()?;
+        debug!(count = items.len(), "pushing items");
 
-            chunks.push(Body { embeds: chunk });
-        }
-
-        for v in chunks.iter() {
-            self.client
-                .post(self.url.as_ref())
-                .json(v)
-                .send()
-                .await?
-                .error_for_status()?;
+        for v in bodies(&self.template, items)?.iter() {
+            self.limiter.acquire().await;
+            retry::send(self.client.post(self.url.as_ref()).json(v)).await?;
         }
 
         Ok(())
@@ -89,6 +137,43 @@ impl Sink for Discord {
     }
 }
 
+/// Maximum number of embeds per message
+const EMBED_LIMIT: usize = 10;
+
+/// Returns the request bodies for the items
+fn bodies<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<Body<'a>>>
+where
+    T: FeedItem<'a>,
+{
+    let mut embeds = items
+        .iter()
+        .map(|item| skip_failed_render(template.embed(item)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .peekable();
+
+    let mut bodies = Vec::new();
+    while embeds.peek().is_some() {
+        bodies.push(Body {
+            embeds: embeds.by_ref().take(EMBED_LIMIT).collect(),
+        });
+    }
+
+    Ok(bodies)
+}
+
+/// Returns the JSON request bodies that would be sent for the items
+pub fn render<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    bodies(template, items)?
+        .iter()
+        .map(|v| Ok(serde_json::to_string_pretty(v)?))
+        .collect()
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct Body<'a> {
@@ -101,7 +186,8 @@ struct EmbedObject<'a> {
     title: String,
     description: String,
     url: &'a str,
-    timestamp: DateTime<FixedOffset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<DateTime<FixedOffset>>,
     author: EmbedAuthor<'a>,
     footer: EmbedFooter<'a>,
     provider: EmbedProvider<'a>,
@@ -130,10 +216,10 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Default)]
+#[derive(Debug, Clone, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 struct EmbedAuthor<'a> {
-    name: &'a str,
+    name: Cow<'a, str>,
     url: Option<&'a str>,
 }
 
@@ -146,7 +232,7 @@ where
     fn try_from_item(value: &'a T) -> std::result::Result<Self, Self::Error> {
         Ok(match value.authors().first() {
             Some(v) => Self {
-                name: v.name,
+                name: v.name.into(),
                 url: v.uri,
             },
             None => Self::default(),
@@ -154,16 +240,10 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize)]
-#[serde(rename_all = "camelCase")]
-struct EmbedImage<'a> {
-    url: &'a str,
-}
-
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct EmbedFooter<'a> {
-    text: &'a str,
+    text: Cow<'a, str>,
 }
 
 impl<'a, T> TryFromItem<'a, T> for EmbedFooter<'a>
@@ -175,8 +255,8 @@ where
     fn try_from_item(value: &'a T) -> std::result::Result<Self, Self::Error> {
         Ok(Self {
             text: match value.source() {
-                Some(v) => v.title,
-                None => "",
+                Some(v) => v.title.into(),
+                None => "".into(),
             },
         })
     }
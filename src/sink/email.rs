@@ -0,0 +1,441 @@
+use crate::{
+    error::{Error, FeedError},
+    feed::item::{FeedItem, TryFromItem},
+    template::{Context, Template},
+    Result,
+};
+
+use super::{
+    escape_html, invalid,
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, InvalidOption, Sink,
+};
+
+use std::{str::FromStr, sync::Arc};
+
+use async_trait::async_trait;
+use lettre::{
+    address::AddressError,
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Deserialize;
+use tracing::debug;
+
+/// Rate limit of a server, if not configured otherwise
+pub const DEFAULT_QUOTA: Quota = Quota::per_minute(30);
+
+/// How the connection to the server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encryption {
+    /// Upgrades the connection with `STARTTLS`
+    #[default]
+    Starttls,
+    /// Implicit TLS
+    Tls,
+    None,
+}
+
+impl Encryption {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Encryption::Starttls => 587,
+            Encryption::Tls => 465,
+            Encryption::None => 25,
+        }
+    }
+}
+
+/// Connection to the SMTP server
+#[derive(Debug, Clone, PartialEq)]
+pub struct Server {
+    pub host: String,
+    pub port: u16,
+    pub encryption: Encryption,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Server {
+    /// Creates the transport. The connection is established on the first
+    /// mail.
+    pub fn transport(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+        let builder = match self.encryption {
+            Encryption::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            Encryption::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.host)?
+            }
+            #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+            Encryption::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)?,
+            #[cfg(not(any(feature = "native-tls", feature = "rustls-tls")))]
+            _ => {
+                return Err(Error::Config(
+                    "TLS is not supported by this build".to_string(),
+                ))
+            }
+        };
+
+        let builder = match self.credentials()? {
+            Some(credentials) => builder.credentials(credentials),
+            None => builder,
+        };
+
+        Ok(builder.port(self.port).build())
+    }
+
+    pub fn credentials(&self) -> Result<Option<Credentials>> {
+        match (&self.username, &self.password) {
+            (Some(username), Some(password)) => {
+                Ok(Some(Credentials::new(username.clone(), password.clone())))
+            }
+            (None, None) => Ok(None),
+            _ => Err(Error::Config(
+                "email username and password must be set together".to_string(),
+            )),
+        }
+    }
+}
+
+/// Sender, recipients and subject of the mails
+#[derive(Debug, Clone, PartialEq)]
+pub struct Headers {
+    pub from: Template,
+    pub to: Vec<Template>,
+    /// Defaults to the title of the item
+    pub subject: Option<Template>,
+}
+
+impl Headers {
+    /// Checks that the templates render valid addresses
+    pub fn validate(&self) -> std::result::Result<(), InvalidOption> {
+        self.from
+            .render_sample()
+            .map_err(Error::from)
+            .and_then(|v| parse_mailbox(&v))
+            .map_err(invalid("from"))?;
+
+        if self.to.is_empty() {
+            let err = Error::Config("email has no recipients".to_string());
+            return Err(("to", Box::new(err)));
+        }
+        for t in self.to.iter() {
+            t.render_sample()
+                .map_err(Error::from)
+                .and_then(|v| parse_mailboxes(&v))
+                .map_err(invalid("to"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Builds a mail of the items. The headers are rendered with the first
+    /// item.
+    fn message<'a, T>(&self, items: &'a [T]) -> Result<Message>
+    where
+        T: FeedItem<'a>,
+    {
+        let Some(first) = items.first() else {
+            return Err(Error::Sink("no items to send".to_string()));
+        };
+        let ctx = Context::try_from_item(first)?;
+
+        let mut builder = Message::builder().from(parse_mailbox(&self.from.render(&ctx)?)?);
+        for t in self.to.iter() {
+            for mailbox in parse_mailboxes(&t.render(&ctx)?)? {
+                builder = builder.to(mailbox);
+            }
+        }
+
+        let mut subject = match &self.subject {
+            Some(t) => t.render(&ctx)?,
+            None => ctx.title.clone().unwrap_or_default(),
+        };
+        if items.len() > 1 {
+            subject.push_str(&format!(" (+{} more)", items.len() - 1));
+        }
+
+        let bodies = items.iter().map(body).collect::<Result<Vec<_>>>()?;
+        let (text, html): (Vec<_>, Vec<_>) = bodies.into_iter().unzip();
+
+        let html = format!(
+            "<!DOCTYPE html>\n<html>\n<body>\n{}\n</body>\n</html>\n",
+            html.join("\n<hr>\n")
+        );
+
+        let body = MultiPart::alternative_plain_html(text.join("\n\n---\n\n"), html);
+
+        Ok(builder.subject(subject.trim()).multipart(body)?)
+    }
+
+    /// Returns the mails of the items, either one per item or a single one
+    fn messages<'a, T>(&self, items: &'a [T], digest: bool) -> Result<Vec<Message>>
+    where
+        T: FeedItem<'a>,
+    {
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+        if digest {
+            return Ok(skip_failed_render(self.message(items))?
+                .into_iter()
+                .collect());
+        }
+
+        Ok(items
+            .chunks(1)
+            .map(|item| skip_failed_render(self.message(item)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+}
+
+pub struct Email {
+    server: Server,
+    headers: Headers,
+    digest: bool,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl Email {
+    pub fn new(
+        server: Server,
+        headers: Headers,
+        digest: bool,
+        limiter: Arc<RateLimiter>,
+    ) -> Result<Self> {
+        Ok(Self {
+            transport: server.transport()?,
+            server,
+            headers,
+            digest,
+            limiter,
+        })
+    }
+}
+
+impl std::fmt::Debug for Email {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Email")
+            .field("host", &self.server.host)
+            .field("port", &self.server.port)
+            .field("encryption", &self.server.encryption)
+            .field("digest", &self.digest)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Sink for Email {
+    #[tracing::instrument(
+        name = "push",
+        skip(self, items),
+        fields(
+            host = %self.server.host,
+            port = self.server.port,
+        )
+        level = "debug"
+    )]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        debug!(count = items.len(), "pushing items");
+
+        for message in self.headers.messages(items, self.digest)? {
+            self.limiter.acquire().await;
+            self.transport.send(message).await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "shutdown",
+        skip(self),
+        fields(
+            host = %self.server.host,
+            port = self.server.port,
+        )
+        level = "debug"
+    )]
+    async fn shutdown(self) -> Result<()> {
+        debug!("shutting down");
+        Ok(())
+    }
+}
+
+/// Returns the mails that would be sent for the items
+pub fn render<'a, T>(headers: &Headers, digest: bool, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    Ok(headers
+        .messages(items, digest)?
+        .iter()
+        .map(|v| String::from_utf8_lossy(&v.formatted()).into_owned())
+        .collect())
+}
+
+/// Returns the text and HTML body of the item. The HTML body contains the
+/// original content or description, plain text is escaped.
+fn body<'a, T>(item: &'a T) -> Result<(String, String)>
+where
+    T: FeedItem<'a>,
+{
+    let title = item
+        .title_as_text()
+        .map_err(FeedError::from)?
+        .map(|v| v.trim().to_string())
+        .unwrap_or_default();
+    let link = item.link();
+
+    let (html, text) = match item.content() {
+        Some(v) if item.content_is_html() => (Some(v.to_string()), item.content_as_text()),
+        Some(v) => (Some(text_to_html(v)), item.content_as_text()),
+        None => {
+            let html = match item.description() {
+                Some(v) if item.description_is_html() => Some(v.to_string()),
+                v => v.map(text_to_html),
+            };
+            (html, item.description_as_text())
+        }
+    };
+    let text = text
+        .map_err(FeedError::from)?
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+
+    let mut plain = title.clone();
+    if let Some(text) = text {
+        plain.push_str("\n\n");
+        plain.push_str(&text);
+    }
+    if let Some(url) = link {
+        plain.push_str("\n\n");
+        plain.push_str(url);
+    }
+
+    let mut rich = match link {
+        Some(url) => format!(
+            "<h2><a href=\"{}\">{}</a></h2>",
+            escape_html(url),
+            escape_html(&title)
+        ),
+        None => format!("<h2>{}</h2>", escape_html(&title)),
+    };
+    if let Some(html) = html {
+        rich.push_str("\n<div>");
+        rich.push_str(&html);
+        rich.push_str("</div>");
+    }
+
+    Ok((plain, rich))
+}
+
+/// Escapes plain text for the HTML body and keeps its line breaks
+fn text_to_html(s: &str) -> String {
+    escape_html(s.trim()).replace('\n', "<br>\n")
+}
+
+/// Parses an address with an optional display name, e.g. `Name <address>`.
+/// The name is taken as is, so it may contain characters that would have to
+/// be quoted.
+fn parse_mailbox(s: &str) -> Result<Mailbox> {
+    let s = s.trim();
+    let err = |e: AddressError| Error::Address(format!("\"{s}\": {e}"));
+
+    let Some((name, address)) = s.strip_suffix('>').and_then(|v| v.rsplit_once('<')) else {
+        return Mailbox::from_str(s).map_err(err);
+    };
+
+    let name = name.trim();
+    let name = match name.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        Some(v) => v.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => name.to_string(),
+    };
+    let address = Address::from_str(address.trim()).map_err(err)?;
+
+    Ok(Mailbox::new(Some(name).filter(|v| !v.is_empty()), address))
+}
+
+/// Parses a comma separated list of addresses. Commas before the first `@`
+/// of an address are part of its display name.
+fn parse_mailboxes(s: &str) -> Result<Vec<Mailbox>> {
+    let mut mailboxes = Vec::new();
+    let mut start = 0;
+
+    for (i, _) in s.match_indices(',') {
+        if s[start..i].contains('@') {
+            mailboxes.push(parse_mailbox(&s[start..i])?);
+            start = i + 1;
+        }
+    }
+    if !s[start..].trim().is_empty() || mailboxes.is_empty() {
+        mailboxes.push(parse_mailbox(&s[start..])?);
+    }
+
+    Ok(mailboxes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::feed::json;
+
+    #[test]
+    fn parse_addresses() {
+        let cases = [
+            ("a@example.com", Some(vec!["a@example.com"])),
+            ("Feed <a@example.com>", Some(vec!["Feed <a@example.com>"])),
+            (
+                "Doe, John (News) <a@example.com>",
+                Some(vec!["\"Doe, John (News)\" <a@example.com>"]),
+            ),
+            (
+                "\"Say \\\"hi\\\"\" <a@example.com>",
+                Some(vec!["\"Say \\\"hi\\\"\" <a@example.com>"]),
+            ),
+            (
+                "a@example.com, Doe, John <b@example.com>,",
+                Some(vec!["a@example.com", "\"Doe, John\" <b@example.com>"]),
+            ),
+            ("", None),
+            ("Feed <invalid>", None),
+        ];
+
+        for (value, expected) in cases {
+            let mailboxes = parse_mailboxes(value)
+                .ok()
+                .map(|v| v.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+            let expected = expected.map(|v| v.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+            assert_eq!(mailboxes, expected, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn escape_text_content() {
+        let cases = [
+            (r#""content_text": "1 < 2\n<b>""#, "1 &lt; 2<br>\n&lt;b&gt;"),
+            (r#""content_html": "<p>1 &lt; 2</p>""#, "<p>1 &lt; 2</p>"),
+            (r#""summary": "a & b""#, "a &amp; b"),
+        ];
+
+        for (fields, expected) in cases {
+            let json = format!(r#"{{"id": "1", "title": "T", {fields}}}"#);
+            let item: json::Item = serde_json::from_str(&json).unwrap();
+            let (_, html) = body(&item).unwrap();
+            assert_eq!(
+                html,
+                format!("<h2>T</h2>\n<div>{expected}</div>"),
+                "{fields}"
+            );
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    error::{Error, FeedError},
+    feed::item::{FeedItem, TryFromItem},
+    retry,
+    template::{Context, Template},
+    Result,
+};
+
+use super::{
+    escape_html,
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, Sink,
+};
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Rate limit of a room, if not configured otherwise. Synapse allows 0.2
+/// messages per second with bursts of 10 by default.
+pub const DEFAULT_QUOTA: Quota = Quota::per_minute(12);
+
+#[derive(Debug)]
+pub struct Matrix {
+    homeserver: Url,
+    room_id: String,
+    /// URL of the `send` endpoint, without the transaction ID
+    url: Url,
+    access_token: String,
+    template: Templates,
+    limiter: Arc<RateLimiter>,
+    client: Client,
+}
+
+impl Matrix {
+    /// Returns the URL to send message events to the room
+    pub fn url(homeserver: &Url, room_id: &str) -> Result<Url> {
+        let mut url = homeserver.clone();
+        url.path_segments_mut()
+            .map_err(|_| {
+                Error::Config(format!(
+                    "invalid Matrix homeserver URL with scheme \"{}\"",
+                    homeserver.scheme()
+                ))
+            })?
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3", "rooms", room_id])
+            .extend(["send", "m.room.message"]);
+
+        Ok(url)
+    }
+
+    pub fn new(
+        homeserver: Url,
+        room_id: String,
+        access_token: String,
+        template: Templates,
+        limiter: Arc<RateLimiter>,
+        client: Client,
+    ) -> Result<Self> {
+        Ok(Self {
+            url: Self::url(&homeserver, &room_id)?,
+            homeserver,
+            room_id,
+            access_token,
+            template,
+            limiter,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for Matrix {
+    #[tracing::instrument(
+        name = "push",
+        skip(self, items),
+        fields(
+            homeserver = %self.homeserver,
+            room_id = %self.room_id,
+        )
+        level = "debug"
+    )]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        debug!(count = items.len(), "pushing items");
+
+        let messages = items
+            .iter()
+            .map(|item| {
+                let message = skip_failed_render(self.template.message(item))?;
+                Ok(message.map(|v| (transaction_id(item), v)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (txn_id, message) in messages.iter().flatten() {
+            let mut url = self.url.clone();
+            url.path_segments_mut().unwrap().push(txn_id);
+
+            self.limiter.acquire().await;
+            retry::send(
+                self.client
+                    .put(url)
+                    .bearer_auth(&self.access_token)
+                    .json(message),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "shutdown",
+        skip(self),
+        fields(
+            homeserver = %self.homeserver,
+            room_id = %self.room_id,
+        )
+        level = "debug"
+    )]
+    async fn shutdown(self) -> Result<()> {
+        debug!("shutting down");
+        Ok(())
+    }
+}
+
+/// Returns the JSON request bodies that would be sent for the items
+pub fn render<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    items
+        .iter()
+        .filter_map(|item| skip_failed_render(template.message(item)).transpose())
+        .map(|v| Ok(serde_json::to_string_pretty(&v?)?))
+        .collect()
+}
+
+/// Returns the transaction ID of the item's event.
+///
+/// The homeserver ignores events whose transaction ID was already used, so
+/// an item is not posted twice if a push is retried after a partial failure.
+/// Items without an ID get a random one.
+fn transaction_id<'a, T>(item: &'a T) -> String
+where
+    T: FeedItem<'a>,
+{
+    let feed = item.source().map(|v| v.name).unwrap_or_default();
+
+    match item.id() {
+        Some(id) => format!("{}.{feed}.{id}", env!("CARGO_PKG_NAME")),
+        None => format!("{}.{:016x}", env!("CARGO_PKG_NAME"), fastrand::u64(..)),
+    }
+}
+
+/// Templates for the parts of the message. The rendered text is escaped in
+/// the formatted body.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Templates {
+    title: Option<Template>,
+    description: Option<Template>,
+}
+
+impl Templates {
+    /// Renders the message of the item
+    fn message<'a, T>(&self, item: &'a T) -> Result<Message>
+    where
+        T: FeedItem<'a>,
+    {
+        let mut title = item
+            .title_as_text()
+            .map_err(FeedError::from)?
+            .map(|v| v.trim().to_string());
+        let mut description = item
+            .description_as_text()
+            .map_err(FeedError::from)?
+            .map(|v| v.trim().to_string());
+
+        if self.title.is_some() || self.description.is_some() {
+            let ctx = Context::try_from_item(item)?;
+
+            if let Some(t) = &self.title {
+                title = Some(t.render(&ctx)?.trim().to_string());
+            }
+            if let Some(t) = &self.description {
+                description = Some(t.render(&ctx)?.trim().to_string());
+            }
+        }
+
+        let title = title
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| FeedError::Item("title is missing".to_string()))?;
+
+        Ok(Message::new(
+            title,
+            description.filter(|v| !v.is_empty()),
+            item.link(),
+        ))
+    }
+}
+
+/// Content of an `m.room.message` event
+#[derive(Debug, Serialize)]
+struct Message {
+    msgtype: &'static str,
+    body: String,
+    format: &'static str,
+    formatted_body: String,
+}
+
+impl Message {
+    fn new(title: String, description: Option<String>, link: Option<&str>) -> Self {
+        let mut body = title.clone();
+        let mut formatted_body = match link {
+            Some(url) => format!(
+                "<strong><a href=\"{}\">{}</a></strong>",
+                escape_html(url),
+                escape_html(&title)
+            ),
+            None => format!("<strong>{}</strong>", escape_html(&title)),
+        };
+
+        if let Some(description) = description {
+            body.push_str("\n\n");
+            body.push_str(&description);
+            formatted_body.push_str("<br><br>");
+            formatted_body.push_str(&escape_html(&description).replace('\n', "<br>"));
+        }
+
+        if let Some(url) = link {
+            body.push_str("\n\n");
+            body.push_str(url);
+        }
+
+        Self {
+            msgtype: "m.text",
+            body,
+            format: "org.matrix.custom.html",
+            formatted_body,
+        }
+    }
+}
@@ -1,23 +1,102 @@
 pub mod custom;
 pub mod discord;
+pub mod email;
+pub mod matrix;
+pub mod preview;
+pub mod rate_limit;
 pub mod slack;
+pub mod teams;
+pub mod telegram;
+pub mod webhook;
 
-use crate::{feed::item::FeedItem, Result};
+use crate::{error::Error, feed::item::FeedItem, template::Template, Result};
 
-use self::{custom::Custom, discord::Discord, slack::Slack};
+use self::{
+    custom::Custom,
+    discord::Discord,
+    email::{Email, Encryption, Headers, Server},
+    matrix::Matrix,
+    rate_limit::{Quota, RateLimiters},
+    slack::Slack,
+    teams::Teams,
+    telegram::{Chat, ChatId, ParseMode, Telegram},
+    webhook::{Auth, Delivery, Method, Webhook},
+};
+
+use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
-use reqwest::Client;
+use reqwest::{Client, Url};
 use serde::Deserialize;
+use tracing::warn;
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case", tag = "type")]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", deny_unknown_fields)]
 pub enum SinkOptions {
     Discord {
         url: String,
+        #[serde(default)]
+        template: discord::Templates,
+        rate_limit: Option<Quota>,
     },
     Slack {
         url: String,
+        #[serde(default)]
+        template: slack::Templates,
+        rate_limit: Option<Quota>,
+    },
+    Teams {
+        url: String,
+        #[serde(default)]
+        template: teams::Templates,
+        rate_limit: Option<Quota>,
+    },
+    Webhook {
+        url: String,
+        #[serde(default)]
+        method: Method,
+        #[serde(default)]
+        headers: HashMap<String, Template>,
+        auth: Option<Auth>,
+        body: Option<Template>,
+        content_type: Option<String>,
+        #[serde(default)]
+        delivery: Delivery,
+        rate_limit: Option<Quota>,
+    },
+    Matrix {
+        homeserver: String,
+        access_token: String,
+        room_id: String,
+        #[serde(default)]
+        template: matrix::Templates,
+        rate_limit: Option<Quota>,
+    },
+    Telegram {
+        token: String,
+        chat_id: ChatId,
+        #[serde(default)]
+        parse_mode: ParseMode,
+        #[serde(default = "default_link_preview")]
+        link_preview: bool,
+        #[serde(default)]
+        template: telegram::Templates,
+        api_url: Option<String>,
+        rate_limit: Option<Quota>,
+    },
+    Email {
+        host: String,
+        port: Option<u16>,
+        #[serde(default)]
+        encryption: Encryption,
+        username: Option<String>,
+        password: Option<String>,
+        from: Template,
+        to: Vec<Template>,
+        subject: Option<Template>,
+        #[serde(default)]
+        digest: bool,
+        rate_limit: Option<Quota>,
     },
     Custom {
         command: String,
@@ -26,11 +105,123 @@ pub enum SinkOptions {
     },
 }
 
+fn default_link_preview() -> bool {
+    true
+}
+
 impl SinkOptions {
-    pub fn sink(self, client: &Client) -> Result<AnySink> {
+    /// Creates the sink. Sinks posting to the same URL share a rate limiter.
+    pub fn sink(self, client: &Client, limiters: &RateLimiters) -> Result<AnySink> {
         let sink = match self {
-            SinkOptions::Discord { url } => AnySink::Discord(Discord::new(url, client.clone())?),
-            SinkOptions::Slack { url } => AnySink::Slack(Slack::new(url, client.clone())?),
+            SinkOptions::Discord {
+                url,
+                template,
+                rate_limit,
+            } => {
+                let url = parse_url("url", &url)?;
+                let limiter = limiters.get(&url, rate_limit.unwrap_or(discord::DEFAULT_QUOTA))?;
+                AnySink::Discord(Discord::new(url, template, limiter, client.clone())?)
+            }
+            SinkOptions::Slack {
+                url,
+                template,
+                rate_limit,
+            } => {
+                let url = parse_url("url", &url)?;
+                let limiter = limiters.get(&url, rate_limit.unwrap_or(slack::DEFAULT_QUOTA))?;
+                AnySink::Slack(Slack::new(url, template, limiter, client.clone())?)
+            }
+            SinkOptions::Teams {
+                url,
+                template,
+                rate_limit,
+            } => {
+                let url = parse_url("url", &url)?;
+                let limiter = limiters.get(&url, rate_limit.unwrap_or(teams::DEFAULT_QUOTA))?;
+                AnySink::Teams(Teams::new(url, template, limiter, client.clone())?)
+            }
+            SinkOptions::Webhook {
+                url,
+                method,
+                headers,
+                auth,
+                body,
+                content_type,
+                delivery,
+                rate_limit,
+            } => {
+                let url = parse_url("url", &url)?;
+                let limiter = limiters.get(&url, rate_limit.unwrap_or(webhook::DEFAULT_QUOTA))?;
+                let options = webhook::Options {
+                    method,
+                    headers,
+                    auth,
+                    body,
+                    content_type,
+                    delivery,
+                };
+                AnySink::Webhook(Webhook::new(url, options, limiter, client.clone())?)
+            }
+            SinkOptions::Matrix {
+                homeserver,
+                access_token,
+                room_id,
+                template,
+                rate_limit,
+            } => {
+                let homeserver = parse_url("homeserver", &homeserver)?;
+                // The limit applies per room
+                let url = Matrix::url(&homeserver, &room_id)?;
+                let limiter = limiters.get(&url, rate_limit.unwrap_or(matrix::DEFAULT_QUOTA))?;
+                AnySink::Matrix(Matrix::new(
+                    homeserver,
+                    room_id,
+                    access_token,
+                    template,
+                    limiter,
+                    client.clone(),
+                )?)
+            }
+            SinkOptions::Telegram {
+                token,
+                chat_id,
+                parse_mode,
+                link_preview,
+                template,
+                api_url,
+                rate_limit,
+            } => {
+                let api_url = parse_url(
+                    "api_url",
+                    api_url.as_deref().unwrap_or(telegram::DEFAULT_API_URL),
+                )?;
+                // The limit applies per chat, not per bot
+                let mut key = Telegram::url(&api_url, &token)?;
+                key.set_fragment(Some(&chat_id.to_string()));
+                let limiter = limiters.get(&key, rate_limit.unwrap_or(telegram::DEFAULT_QUOTA))?;
+                let chat = Chat {
+                    id: chat_id,
+                    parse_mode,
+                    link_preview,
+                    template,
+                };
+                AnySink::Telegram(Telegram::new(
+                    api_url,
+                    &token,
+                    chat,
+                    limiter,
+                    client.clone(),
+                )?)
+            }
+            SinkOptions::Email {
+                rate_limit, digest, ..
+            } => {
+                let (server, headers) = self.email().unwrap();
+                // The limit applies per server
+                let url = parse_url("host", &format!("smtp://{}:{}", server.host, server.port))?;
+                let limiter = limiters.get(&url, rate_limit.unwrap_or(email::DEFAULT_QUOTA))?;
+                AnySink::Email(Email::new(server, headers, digest, limiter)?)
+            }
             SinkOptions::Custom { command, arguments } => {
                 AnySink::Custom(Custom::new(command, arguments)?)
             }
@@ -38,6 +229,277 @@ impl SinkOptions {
 
         Ok(sink)
     }
+
+    /// Checks the options without creating the sink and returns the key of
+    /// the invalid option together with the error
+    pub fn validate(&self) -> std::result::Result<(), InvalidOption> {
+        let rate_limit = match self {
+            SinkOptions::Discord {
+                url, rate_limit, ..
+            }
+            | SinkOptions::Slack {
+                url, rate_limit, ..
+            }
+            | SinkOptions::Teams {
+                url, rate_limit, ..
+            } => {
+                parse_url("url", url).map_err(invalid("url"))?;
+                rate_limit
+            }
+            SinkOptions::Webhook {
+                url, rate_limit, ..
+            } => {
+                parse_url("url", url).map_err(invalid("url"))?;
+                self.webhook().unwrap().validate()?;
+                rate_limit
+            }
+            SinkOptions::Matrix {
+                homeserver,
+                access_token,
+                room_id,
+                rate_limit,
+                ..
+            } => {
+                if access_token.is_empty() {
+                    let err = Error::Config("Matrix access token is empty".to_string());
+                    return Err(("access_token", Box::new(err)));
+                }
+                if !room_id.starts_with('!') {
+                    let err = Error::Config(format!(
+                        "invalid Matrix room ID \"{room_id}\", it must start with \"!\""
+                    ));
+                    return Err(("room_id", Box::new(err)));
+                }
+                parse_url("homeserver", homeserver)
+                    .and_then(|v| Matrix::url(&v, room_id))
+                    .map_err(invalid("homeserver"))?;
+                rate_limit
+            }
+            SinkOptions::Telegram {
+                token,
+                api_url,
+                rate_limit,
+                ..
+            } => {
+                if token.is_empty() {
+                    let err = Error::Config("Telegram bot token is empty".to_string());
+                    return Err(("token", Box::new(err)));
+                }
+                parse_url(
+                    "api_url",
+                    api_url.as_deref().unwrap_or(telegram::DEFAULT_API_URL),
+                )
+                .and_then(|v| Telegram::url(&v, token))
+                .map_err(invalid("api_url"))?;
+                rate_limit
+            }
+            SinkOptions::Email { rate_limit, .. } => {
+                let (server, headers) = self.email().unwrap();
+                server.credentials().map_err(invalid("username"))?;
+                server.transport().map_err(invalid("host"))?;
+                headers.validate()?;
+                rate_limit
+            }
+            SinkOptions::Custom { command, .. } => {
+                if !custom::command_exists(command) {
+                    let err =
+                        Error::Config(format!("command \"{command}\" not found or not executable"));
+                    return Err(("command", Box::new(err)));
+                }
+                &None
+            }
+        };
+
+        if let Some(quota) = rate_limit {
+            quota.validate().map_err(invalid("rate_limit"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the payloads the sink would send for the items, without
+    /// sending them
+    pub fn render<'a, T>(&self, items: &'a [T]) -> Result<Vec<String>>
+    where
+        T: FeedItem<'a>,
+    {
+        match self {
+            SinkOptions::Discord { template, .. } => discord::render(template, items),
+            SinkOptions::Slack { template, .. } => slack::render(template, items),
+            SinkOptions::Teams { template, .. } => teams::render(template, items),
+            SinkOptions::Webhook { .. } => webhook::render(&self.webhook().unwrap(), items),
+            SinkOptions::Matrix { template, .. } => matrix::render(template, items),
+            SinkOptions::Telegram {
+                chat_id,
+                parse_mode,
+                link_preview,
+                template,
+                ..
+            } => {
+                let chat = Chat {
+                    id: chat_id.clone(),
+                    parse_mode: *parse_mode,
+                    link_preview: *link_preview,
+                    template: template.clone(),
+                };
+                telegram::render(&chat, items)
+            }
+            SinkOptions::Email { digest, .. } => {
+                let (_, headers) = self.email().unwrap();
+                email::render(&headers, *digest, items)
+            }
+            SinkOptions::Custom { .. } => custom::render(items),
+        }
+    }
+}
+
+impl SinkOptions {
+    /// Returns the request options of a webhook sink
+    fn webhook(&self) -> Option<webhook::Options> {
+        let SinkOptions::Webhook {
+            method,
+            headers,
+            auth,
+            body,
+            content_type,
+            delivery,
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        Some(webhook::Options {
+            method: *method,
+            headers: headers.clone(),
+            auth: auth.clone(),
+            body: body.clone(),
+            content_type: content_type.clone(),
+            delivery: *delivery,
+        })
+    }
+
+    /// Returns the server and headers of an email sink
+    fn email(&self) -> Option<(Server, Headers)> {
+        let SinkOptions::Email {
+            host,
+            port,
+            encryption,
+            username,
+            password,
+            from,
+            to,
+            subject,
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        let server = Server {
+            host: host.clone(),
+            port: port.unwrap_or(encryption.default_port()),
+            encryption: *encryption,
+            username: username.clone(),
+            password: password.clone(),
+        };
+        let headers = Headers {
+            from: from.clone(),
+            to: to.clone(),
+            subject: subject.clone(),
+        };
+
+        Some((server, headers))
+    }
+}
+
+/// Key of an invalid sink option together with the error
+pub type InvalidOption = (&'static str, Box<Error>);
+
+/// Returns a function that marks an error as caused by the option `key`
+fn invalid(key: &'static str) -> impl FnOnce(Error) -> InvalidOption {
+    move |err| (key, Box::new(err))
+}
+
+/// Parses the URL of the option `field`. The URL is not included in the
+/// error, since it may contain a secret, e.g. a webhook token.
+fn parse_url(field: &str, url: &str) -> Result<Url> {
+    Url::parse(url).map_err(|e| Error::Config(format!("invalid URL in \"{field}\": {e}")))
+}
+
+/// Skips an item whose template cannot be rendered or renders an invalid
+/// email address, so that a single item does not fail the whole push. Other
+/// errors are returned.
+fn skip_failed_render<T>(res: Result<T>) -> Result<Option<T>> {
+    match res {
+        Err(err @ (Error::Template(_) | Error::Address(_))) => {
+            warn!(error = %err, "skipping item, it could not be rendered");
+            Ok(None)
+        }
+        res => res.map(Some),
+    }
+}
+
+/// Escapes text for HTML formatted messages
+fn escape_html(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Unit in which the length of a text is measured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Length {
+    Chars,
+    /// UTF-16 code units, as counted by JavaScript and Telegram
+    Utf16,
+}
+
+impl Length {
+    pub fn count(&self, s: &str) -> usize {
+        s.chars().map(|c| self.of(c)).sum()
+    }
+
+    fn of(&self, c: char) -> usize {
+        match self {
+            Length::Chars => 1,
+            Length::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// Cuts off the text, so that it is at most `limit` long including the
+/// ellipsis it ends with
+pub fn truncate(s: &str, limit: usize, length: Length) -> String {
+    if length.count(s) <= limit {
+        return s.to_string();
+    }
+
+    let mut result = String::with_capacity(s.len());
+    let mut len = 0;
+
+    for c in s.chars() {
+        // Leaves room for the ellipsis
+        if len + length.of(c) > limit.saturating_sub(1) {
+            break;
+        }
+        len += length.of(c);
+        result.push(c);
+    }
+
+    result.truncate(result.trim_end().len());
+    result.push('…');
+    result
 }
 
 #[async_trait]
@@ -53,6 +515,11 @@ pub trait Sink {
 pub enum AnySink {
     Discord(discord::Discord),
     Slack(slack::Slack),
+    Teams(teams::Teams),
+    Webhook(webhook::Webhook),
+    Matrix(matrix::Matrix),
+    Telegram(telegram::Telegram),
+    Email(email::Email),
     Custom(custom::Custom),
 }
 
@@ -66,6 +533,11 @@ impl Sink for AnySink {
         match self {
             AnySink::Discord(s) => s.push(items).await,
             AnySink::Slack(s) => s.push(items).await,
+            AnySink::Teams(s) => s.push(items).await,
+            AnySink::Webhook(s) => s.push(items).await,
+            AnySink::Matrix(s) => s.push(items).await,
+            AnySink::Telegram(s) => s.push(items).await,
+            AnySink::Email(s) => s.push(items).await,
             AnySink::Custom(s) => s.push(items).await,
         }
     }
@@ -75,7 +547,118 @@ impl Sink for AnySink {
         match self {
             AnySink::Discord(s) => s.shutdown().await,
             AnySink::Slack(s) => s.shutdown().await,
+            AnySink::Teams(s) => s.shutdown().await,
+            AnySink::Webhook(s) => s.shutdown().await,
+            AnySink::Matrix(s) => s.shutdown().await,
+            AnySink::Telegram(s) => s.shutdown().await,
+            AnySink::Email(s) => s.shutdown().await,
             AnySink::Custom(s) => s.shutdown().await,
         }
     }
 }
+
+/// A sink that is shared between multiple watchers.
+///
+/// The sink is shut down by the last watcher that shuts down.
+#[async_trait]
+impl<S> Sink for Arc<S>
+where
+    S: Sink + Send + Sync,
+{
+    #[inline]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        self.as_ref().push(items).await
+    }
+
+    async fn shutdown(self) -> Result<()> {
+        match Arc::into_inner(self) {
+            Some(s) => s.shutdown().await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Escapes the characters for which `is_special` returns `true` with a
+/// backslash
+fn escape_markdown(s: &str, is_special: impl Fn(char) -> bool) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if is_special(c) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_text() {
+        let cases = [
+            ("", 0, Length::Chars, ""),
+            ("abc", 3, Length::Chars, "abc"),
+            ("abcd", 3, Length::Chars, "ab…"),
+            ("abcd", 1, Length::Chars, "…"),
+            ("abcd", 0, Length::Chars, "…"),
+            ("ab  cd", 4, Length::Chars, "ab…"),
+            ("äöü", 2, Length::Chars, "ä…"),
+            ("🦀🦀", 2, Length::Chars, "🦀🦀"),
+            // Characters outside the BMP count as two code units
+            ("🦀🦀", 4, Length::Utf16, "🦀🦀"),
+            ("🦀🦀", 3, Length::Utf16, "🦀…"),
+            ("🦀🦀", 2, Length::Utf16, "…"),
+            ("äöü", 2, Length::Utf16, "ä…"),
+        ];
+
+        for (value, limit, length, expected) in cases {
+            let result = truncate(value, limit, length);
+            assert_eq!(result, expected, "{value:?} {limit} {length:?}");
+            assert!(length.count(&result) <= limit.max(1), "{value:?} {limit}");
+        }
+    }
+
+    #[test]
+    fn redact_secrets() {
+        let cases = [
+            (
+                r#"type = "discord"
+                url = "secret""#,
+                "invalid URL in \"url\": relative URL without a base",
+            ),
+            (
+                r#"type = "webhook"
+                url = "https://[secret]/hook""#,
+                "invalid URL in \"url\": invalid IPv6 address",
+            ),
+            (
+                r#"type = "matrix"
+                homeserver = "mailto:secret@example.com"
+                access_token = "token"
+                room_id = "!room""#,
+                "invalid Matrix homeserver URL with scheme \"mailto\"",
+            ),
+            (
+                r#"type = "telegram"
+                token = "secret"
+                chat_id = 1
+                api_url = "tg:secret""#,
+                "invalid Telegram API URL with scheme \"tg\"",
+            ),
+        ];
+
+        for (options, expected) in cases {
+            let options: SinkOptions = toml::from_str(options).unwrap();
+            let err = options.validate().unwrap_err().1.to_string();
+            assert_eq!(err, format!("config error: {expected}"), "{options:?}");
+            assert!(!err.contains("secret"), "{err}");
+        }
+    }
+}
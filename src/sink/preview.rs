@@ -0,0 +1,61 @@
+use crate::{feed::item::FeedItem, Result};
+
+use super::{Sink, SinkOptions};
+
+use std::io::{self, Write};
+
+use async_trait::async_trait;
+use tracing::debug;
+
+/// Writes the payloads that a sink would send to stdout instead of sending
+/// them
+#[derive(Debug)]
+pub struct Preview {
+    id: String,
+    options: SinkOptions,
+}
+
+impl Preview {
+    pub fn new(id: String, options: SinkOptions) -> Self {
+        Self { id, options }
+    }
+}
+
+#[async_trait]
+impl Sink for Preview {
+    #[tracing::instrument(
+        name = "push",
+        skip(self, items),
+        fields(
+            sink = %self.id,
+        )
+        level = "debug"
+    )]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        debug!(count = items.len(), "rendering items");
+
+        let payloads = self.options.render(items)?;
+
+        // The payloads are kept apart from the log on stderr
+        eprintln!(
+            "Sink {}: {} item(s) in {} payload(s)",
+            self.id,
+            items.len(),
+            payloads.len()
+        );
+
+        let mut stdout = io::stdout().lock();
+        for payload in payloads {
+            writeln!(stdout, "{payload}")?;
+        }
+
+        Ok(())
+    }
+
+    async fn shutdown(self) -> Result<()> {
+        Ok(())
+    }
+}
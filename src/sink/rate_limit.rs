@@ -0,0 +1,157 @@
+use crate::{error::Error, Result};
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+use serde::Deserialize;
+use tokio::time;
+use tracing::{debug, warn};
+
+/// Number of requests that are allowed within a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    pub requests: u32,
+    #[serde(with = "humantime_serde")]
+    pub period: Duration,
+}
+
+impl Quota {
+    pub const fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            period: Duration::from_secs(60),
+        }
+    }
+
+    pub const fn per_second(requests: u32) -> Self {
+        Self {
+            requests,
+            period: Duration::from_secs(1),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.requests == 0 || self.period.is_zero() {
+            return Err(Error::Config(
+                "rate limit requests and period must not be zero".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Rate limiters of all destinations, so that sinks posting to the same URL
+/// share their quota
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiters(Arc<Mutex<Limiters>>);
+
+#[derive(Debug, Default)]
+struct Limiters {
+    /// Incremented with each loaded config
+    generation: usize,
+    /// Limiters and the generation of the config that last requested them
+    entries: HashMap<Url, (Arc<RateLimiter>, usize)>,
+}
+
+impl RateLimiters {
+    /// Starts a new config, whose quotas replace those of the previous one
+    pub fn reload(&self) {
+        self.0.lock().unwrap().generation += 1;
+    }
+
+    /// Returns the limiter of the URL.
+    ///
+    /// The limiter is replaced if the quota differs from the previous config.
+    /// If sinks of the same config use different quotas for the URL, the
+    /// first one applies.
+    pub fn get(&self, url: &Url, quota: Quota) -> Result<Arc<RateLimiter>> {
+        quota.validate()?;
+
+        let mut limiters = self.0.lock().unwrap();
+        let generation = limiters.generation;
+
+        match limiters.entries.get_mut(url) {
+            Some((limiter, last)) if limiter.quota == quota => {
+                *last = generation;
+                return Ok(limiter.clone());
+            }
+            Some((limiter, last)) if *last == generation => {
+                // The URL is not logged, it might contain a token
+                warn!(
+                    host = url.host_str().unwrap_or_default(),
+                    used = ?limiter.quota,
+                    ignored = ?quota,
+                    "sinks with the same URL have different rate limits",
+                );
+                return Ok(limiter.clone());
+            }
+            _ => {}
+        }
+
+        let limiter = Arc::new(RateLimiter::new(quota));
+        limiters
+            .entries
+            .insert(url.clone(), (limiter.clone(), generation));
+
+        Ok(limiter)
+    }
+}
+
+/// A token bucket that allows bursts up to the number of requests of the
+/// quota
+#[derive(Debug)]
+pub struct RateLimiter {
+    quota: Quota,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    fn new(quota: Quota) -> Self {
+        Self {
+            quota,
+            bucket: Mutex::new(Bucket {
+                tokens: quota.requests as f64,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request is allowed
+    pub async fn acquire(&self) {
+        let capacity = self.quota.requests as f64;
+        let rate = capacity / self.quota.period.as_secs_f64();
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+                bucket.updated = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+            };
+
+            debug!(?wait, "rate limit reached, waiting");
+            time::sleep(wait).await;
+        }
+    }
+}
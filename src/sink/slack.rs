@@ -1,32 +1,109 @@
 use crate::{
     error::FeedError,
     feed::item::{FeedItem, TryFromItem},
+    retry,
+    template::{self, Template},
     Result,
 };
 
-use super::Sink;
+use super::{
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, Sink,
+};
+
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use reqwest::{Client, IntoUrl, Url};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use slack_bk::{
     blocks::{Block, Context, ContextElement, Divider, Header, Section},
     composition::{MarkdownText, PlainText, Text},
     elements::{Button, Element},
 };
-use tokio::time::{self, Duration};
 use tracing::debug;
 
+/// Templates for the text fields of the blocks
+///
+/// Context elements with an empty result are omitted.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Templates {
+    header: Option<Template>,
+    text: Option<Template>,
+    button: Option<Template>,
+    author: Option<Template>,
+    source: Option<Template>,
+    date: Option<Template>,
+}
+
+impl Templates {
+    fn is_empty(&self) -> bool {
+        self.header.is_none()
+            && self.text.is_none()
+            && self.button.is_none()
+            && self.author.is_none()
+            && self.source.is_none()
+            && self.date.is_none()
+    }
+
+    /// Renders the blocks of the item
+    fn blocks<'a, T>(&self, item: &'a T) -> Result<ItemBlockCollection>
+    where
+        T: FeedItem<'a>,
+    {
+        let mut text = ItemText::try_from_item(item)?;
+
+        if !self.is_empty() {
+            let ctx = template::Context::try_from_item(item)?;
+            let optional = |s: String| Some(s).filter(|s| !s.trim().is_empty());
+
+            if let Some(t) = &self.header {
+                text.header = t.render(&ctx)?;
+            }
+            if let Some(t) = &self.text {
+                text.text = t.render(&ctx)?;
+            }
+            if let Some(t) = &self.button {
+                text.button = t.render(&ctx)?;
+            }
+            if let Some(t) = &self.author {
+                text.author = optional(t.render(&ctx)?);
+            }
+            if let Some(t) = &self.source {
+                text.source = optional(t.render(&ctx)?);
+            }
+            if let Some(t) = &self.date {
+                text.date = optional(t.render(&ctx)?);
+            }
+        }
+
+        Ok(text.into_blocks(item.link()))
+    }
+}
+
+/// Rate limit of a webhook, if not configured otherwise
+pub const DEFAULT_QUOTA: Quota = Quota::per_second(1);
+
 #[derive(Debug)]
 pub struct Slack {
     url: Url,
+    template: Templates,
+    limiter: Arc<RateLimiter>,
     client: Client,
 }
 
 impl Slack {
-    pub fn new<T: IntoUrl>(url: T, client: Client) -> Result<Self> {
+    pub fn new<T: IntoUrl>(
+        url: T,
+        template: Templates,
+        limiter: Arc<RateLimiter>,
+        client: Client,
+    ) -> Result<Self> {
         Ok(Self {
             url: url.into_url()?,
+            template,
+            limiter,
             client,
         })
     }
@@ -46,37 +123,11 @@ impl Sink for Slack {
     where
         T: FeedItem<'a>,
     {
-        let length = items.len();
-        let limit = 10_usize;
-        let chunk_count = (length as f64 / limit as f64).ceil() as usize;
-
-        debug!(count = length, chunks = chunk_count, "pushing items");
-
-        let mut chunks: Vec<Body> = Vec::with_capacity(chunk_count);
-        for i in 0..chunk_count {
-            let pos = i * limit;
-            let chunk = items[pos..(pos + limit).min(length)]
-                .iter()
-                .map(ItemBlockCollection::try_from_item)
-                .collect::<std::result::Result<Vec<ItemBlockCollection>, FeedError>>()?
-                .into_iter()
-                .flatten()
-                .collect();
-
-            chunks.push(Body { blocks: chunk });
-        }
-
-        for (i, v) in chunks.iter().enumerate() {
-            self.client
-                .post(self.url.as_ref())
-                .json(v)
-                .send()
-                .await?
-                .error_for_status()?;
+        debug!(count = items.len(), "pushing items");
 
-            if i != chunks.len() - 1 {
-                time::sleep(Duration::from_millis(1000)).await;
-            }
+        for v in bodies(&self.template, items)?.iter() {
+            self.limiter.acquire().await;
+            retry::send(self.client.post(self.url.as_ref()).json(v)).await?;
         }
 
         Ok(())
@@ -96,6 +147,43 @@ impl Sink for Slack {
     }
 }
 
+/// Maximum number of items per message
+const ITEM_LIMIT: usize = 10;
+
+/// Returns the request bodies for the items
+fn bodies<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<Body>>
+where
+    T: FeedItem<'a>,
+{
+    let mut collections = items
+        .iter()
+        .map(|item| skip_failed_render(template.blocks(item)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .peekable();
+
+    let mut bodies = Vec::new();
+    while collections.peek().is_some() {
+        bodies.push(Body {
+            blocks: collections.by_ref().take(ITEM_LIMIT).flatten().collect(),
+        });
+    }
+
+    Ok(bodies)
+}
+
+/// Returns the JSON request bodies that would be sent for the items
+pub fn render<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    bodies(template, items)?
+        .iter()
+        .map(|v| Ok(serde_json::to_string_pretty(v)?))
+        .collect()
+}
+
 #[derive(Debug, Serialize)]
 struct Body {
     blocks: Vec<Block>,
@@ -103,18 +191,52 @@ struct Body {
 
 type ItemBlockCollection = [Block; 4];
 
-impl<'a, T> TryFromItem<'a, T> for ItemBlockCollection
+/// Text fields of the blocks of an item
+#[derive(Debug)]
+struct ItemText {
+    header: String,
+    text: String,
+    button: String,
+    author: Option<String>,
+    source: Option<String>,
+    date: Option<String>,
+}
+
+impl<'a, T> TryFromItem<'a, T> for ItemText
 where
     T: FeedItem<'a>,
 {
     type Error = FeedError;
 
     fn try_from_item(value: &'a T) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            header: value
+                .title_as_text()?
+                .ok_or_else(|| FeedError::Item("title is missing".to_string()))?,
+            text: value
+                .description_as_text()?
+                .unwrap_or_else(|| "...".to_string()),
+            button: ":link: Open".to_string(),
+            author: value.authors().first().map(|a| match a.uri {
+                Some(url) => format!("<{}|{}>", url, a.name),
+                None => a.name.to_string(),
+            }),
+            source: value.source().map(|s| match s.url {
+                Some(url) => format!("<{}|{}>", url, s.title),
+                None => s.title.to_string(),
+            }),
+            date: value
+                .date()
+                .map(|v| v.format("%d %b %Y %I:%M %p %Z").to_string()),
+        })
+    }
+}
+
+impl ItemText {
+    fn into_blocks(self, link: Option<&str>) -> ItemBlockCollection {
         let header = Header {
             text: Text::PlainText(PlainText {
-                text: value
-                    .title_as_text()?
-                    .ok_or_else(|| FeedError::Item("title is missing".to_string()))?,
+                text: self.header,
                 emoji: false,
             }),
             block_id: None,
@@ -122,19 +244,17 @@ where
 
         let section = Section {
             text: Text::PlainText(PlainText {
-                text: value
-                    .description_as_text()?
-                    .unwrap_or_else(|| "...".to_string()),
+                text: self.text,
                 emoji: false,
             })
             .into(),
             accessory: Element::Button(Button {
                 text: Text::PlainText(PlainText {
-                    text: ":link: Open".to_string(),
+                    text: self.button,
                     emoji: true,
                 }),
                 action_id: "button-action".to_string(),
-                url: value.link().map(|s| s.to_string()),
+                url: link.map(|s| s.to_string()),
                 ..Default::default()
             })
             .into(),
@@ -143,47 +263,30 @@ where
 
         let mut ctx_elements = Vec::with_capacity(3);
 
-        if let Some(a) = value.authors().first() {
-            let author = if let Some(url) = a.uri {
-                format!("<{}|{}>", url, a.name)
-            } else {
-                a.name.to_string()
-            };
-
+        for text in [self.author, self.source].into_iter().flatten() {
             ctx_elements.push(ContextElement::Text(Text::Markdown(MarkdownText {
-                text: author,
+                text,
                 verbatim: false,
             })));
         }
 
-        if let Some(s) = value.source() {
-            let src = if let Some(url) = s.url {
-                format!("<{}|{}>", url, s.title)
-            } else {
-                s.title.to_string()
-            };
-
-            ctx_elements.push(ContextElement::Text(Text::Markdown(MarkdownText {
-                text: src,
-                verbatim: false,
+        if let Some(text) = self.date {
+            ctx_elements.push(ContextElement::Text(Text::PlainText(PlainText {
+                text,
+                emoji: false,
             })));
         }
 
-        ctx_elements.push(ContextElement::Text(Text::PlainText(PlainText {
-            text: value.date().format("%d %b %Y %I:%M %p %Z").to_string(),
-            emoji: false,
-        })));
-
         let context = Context {
             elements: ctx_elements,
             ..Default::default()
         };
 
-        Ok([
+        [
             Block::Header(header),
             Block::Section(section),
             Block::Context(context),
             Block::Divider(Divider::default()),
-        ])
+        ]
     }
 }
@@ -0,0 +1,408 @@
+use crate::{
+    error::FeedError,
+    feed::item::{FeedItem, TryFromItem},
+    retry,
+    template::{Context, Template},
+    Result,
+};
+
+use super::{
+    escape_markdown,
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, truncate, Length, Sink,
+};
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, IntoUrl, Url};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Templates for the texts of the card elements. The rendered text is escaped,
+/// except for the button, which is plain text, and the date, which may use the
+/// `DATE` and `TIME` functions of Adaptive Cards.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Templates {
+    title: Option<Template>,
+    text: Option<Template>,
+    button: Option<Template>,
+    author: Option<Template>,
+    source: Option<Template>,
+    date: Option<Template>,
+}
+
+impl Templates {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.text.is_none()
+            && self.button.is_none()
+            && self.author.is_none()
+            && self.source.is_none()
+            && self.date.is_none()
+    }
+
+    /// Renders the container of the item
+    fn container<'a, T>(&self, item: &'a T) -> Result<Element>
+    where
+        T: FeedItem<'a>,
+    {
+        let mut text = ItemText::try_from_item(item)?;
+
+        if !self.is_empty() {
+            let ctx = Context::try_from_item(item)?;
+            let raw = |t: &Template| -> Result<String> { Ok(t.render(&ctx)?.trim().to_string()) };
+            let render = |t: &Template| -> Result<String> { Ok(escape(&raw(t)?)) };
+            let optional = |s: String| Some(s).filter(|s| !s.is_empty());
+
+            if let Some(t) = &self.title {
+                text.title = render(t)?;
+            }
+            if let Some(t) = &self.text {
+                text.text = optional(render(t)?);
+            }
+            if let Some(t) = &self.button {
+                text.button = raw(t)?;
+            }
+            if let Some(t) = &self.author {
+                text.author = optional(render(t)?);
+            }
+            if let Some(t) = &self.source {
+                text.source = optional(render(t)?);
+            }
+            if let Some(t) = &self.date {
+                text.date = optional(raw(t)?);
+            }
+        }
+
+        Ok(text.into_container(item.link()))
+    }
+}
+
+/// Rate limit of a webhook, if not configured otherwise
+pub const DEFAULT_QUOTA: Quota = Quota::per_second(1);
+
+/// Maximum number of items per card
+const ITEM_LIMIT: usize = 10;
+
+/// Descriptions are cut off, so that a card stays below the message size
+/// limit of Teams
+const DESCRIPTION_LIMIT: usize = 1000;
+
+#[derive(Debug)]
+pub struct Teams {
+    url: Url,
+    template: Templates,
+    limiter: Arc<RateLimiter>,
+    client: Client,
+}
+
+impl Teams {
+    pub fn new<T: IntoUrl>(
+        url: T,
+        template: Templates,
+        limiter: Arc<RateLimiter>,
+        client: Client,
+    ) -> Result<Self> {
+        Ok(Self {
+            url: url.into_url()?,
+            template,
+            limiter,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for Teams {
+    #[tracing::instrument(
+        name = "push",
+        skip(self, items),
+        fields(
+            url = %self.url,
+        )
+        level = "debug"
+    )]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        debug!(count = items.len(), "pushing items");
+
+        for v in bodies(&self.template, items)?.iter() {
+            self.limiter.acquire().await;
+            retry::send(self.client.post(self.url.as_ref()).json(v)).await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "shutdown",
+        skip(self),
+        fields(
+            url = %self.url,
+        )
+        level = "debug"
+    )]
+    async fn shutdown(self) -> Result<()> {
+        debug!("shutting down");
+        Ok(())
+    }
+}
+
+/// Returns the request bodies for the items
+fn bodies<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<Body>>
+where
+    T: FeedItem<'a>,
+{
+    let mut containers = items
+        .iter()
+        .map(|item| skip_failed_render(template.container(item)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .peekable();
+
+    let mut bodies = Vec::new();
+    while containers.peek().is_some() {
+        bodies.push(Body::new(containers.by_ref().take(ITEM_LIMIT).collect()));
+    }
+
+    Ok(bodies)
+}
+
+/// Returns the JSON request bodies that would be sent for the items
+pub fn render<'a, T>(template: &Templates, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    bodies(template, items)?
+        .iter()
+        .map(|v| Ok(serde_json::to_string_pretty(v)?))
+        .collect()
+}
+
+/// A message with a single Adaptive Card attachment
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Body {
+    r#type: &'static str,
+    attachments: [Attachment; 1],
+}
+
+impl Body {
+    fn new(body: Vec<Element>) -> Self {
+        Self {
+            r#type: "message",
+            attachments: [Attachment {
+                content_type: "application/vnd.microsoft.card.adaptive",
+                content_url: None,
+                content: AdaptiveCard {
+                    schema: "http://adaptivecards.io/schemas/adaptive-card.json",
+                    r#type: "AdaptiveCard",
+                    version: "1.4",
+                    body,
+                    msteams: MsTeams { width: "Full" },
+                },
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Attachment {
+    content_type: &'static str,
+    content_url: Option<&'static str>,
+    content: AdaptiveCard,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AdaptiveCard {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    r#type: &'static str,
+    version: &'static str,
+    body: Vec<Element>,
+    msteams: MsTeams,
+}
+
+#[derive(Debug, Serialize)]
+struct MsTeams {
+    width: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+enum Element {
+    TextBlock(TextBlock),
+    Container(Container),
+    ActionSet(ActionSet),
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TextBlock {
+    text: String,
+    wrap: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weight: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_subtle: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spacing: Option<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Container {
+    items: Vec<Element>,
+    separator: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ActionSet {
+    actions: Vec<Action>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type")]
+enum Action {
+    #[serde(rename = "Action.OpenUrl")]
+    OpenUrl { title: String, url: String },
+}
+
+/// Text fields of the card elements of an item
+#[derive(Debug)]
+struct ItemText {
+    title: String,
+    text: Option<String>,
+    button: String,
+    author: Option<String>,
+    source: Option<String>,
+    date: Option<String>,
+}
+
+impl<'a, T> TryFromItem<'a, T> for ItemText
+where
+    T: FeedItem<'a>,
+{
+    type Error = FeedError;
+
+    fn try_from_item(value: &'a T) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            title: escape(
+                value
+                    .title_as_text()?
+                    .ok_or_else(|| FeedError::Item("title is missing".to_string()))?
+                    .trim(),
+            ),
+            text: value
+                .description_as_text()?
+                .map(|v| escape(&truncate(v.trim(), DESCRIPTION_LIMIT, Length::Chars)))
+                .filter(|v| !v.is_empty()),
+            button: "Open".to_string(),
+            author: value.authors().first().map(|a| match a.uri {
+                Some(url) => link(a.name, url),
+                None => escape(a.name),
+            }),
+            source: value.source().map(|s| match s.url {
+                Some(url) => link(s.title, url),
+                None => escape(s.title),
+            }),
+            // Formatted by the client in the local time zone of the user
+            date: value.date().map(|v| {
+                let date = v.with_timezone(&Utc).format("%Y-%m-%dT%H:%M:%SZ");
+                format!("{{{{DATE({date}, SHORT)}}}} {{{{TIME({date})}}}}")
+            }),
+        })
+    }
+}
+
+impl ItemText {
+    fn into_container(self, link: Option<&str>) -> Element {
+        let mut items = Vec::with_capacity(4);
+
+        items.push(Element::TextBlock(TextBlock {
+            text: self.title,
+            wrap: true,
+            size: Some("Medium"),
+            weight: Some("Bolder"),
+            ..Default::default()
+        }));
+
+        if let Some(text) = self.text {
+            items.push(Element::TextBlock(TextBlock {
+                text,
+                wrap: true,
+                ..Default::default()
+            }));
+        }
+
+        let context = [self.author, self.source, self.date]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        if !context.is_empty() {
+            items.push(Element::TextBlock(TextBlock {
+                text: context.join(" · "),
+                wrap: true,
+                size: Some("Small"),
+                is_subtle: Some(true),
+                spacing: Some("Small"),
+                ..Default::default()
+            }));
+        }
+
+        if let Some(url) = link {
+            items.push(Element::ActionSet(ActionSet {
+                actions: vec![Action::OpenUrl {
+                    title: self.button,
+                    url: url.to_string(),
+                }],
+            }));
+        }
+
+        Element::Container(Container {
+            items,
+            separator: true,
+        })
+    }
+}
+
+/// Escapes text, so that it is not interpreted as Markdown
+fn escape(s: &str) -> String {
+    escape_markdown(s, |c| {
+        matches!(
+            c,
+            '\\' | '*'
+                | '_'
+                | '['
+                | ']'
+                | '('
+                | ')'
+                | '~'
+                | '`'
+                | '>'
+                | '#'
+                | '+'
+                | '-'
+                | '.'
+                | '!'
+        )
+    })
+}
+
+fn link(text: &str, url: &str) -> String {
+    format!(
+        "[{}]({})",
+        escape(text),
+        escape_markdown(url, |c| c == ')' || c == '\\')
+    )
+}
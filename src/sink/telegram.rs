@@ -0,0 +1,396 @@
+use crate::{
+    error::{Error, FeedError},
+    feed::item::{FeedItem, TryFromItem},
+    retry,
+    template::{Context, Template},
+    Result,
+};
+
+use super::{
+    escape_html, escape_markdown,
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, truncate, Length, Sink,
+};
+
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+pub const DEFAULT_API_URL: &str = "https://api.telegram.org";
+
+/// Rate limit of a chat, if not configured otherwise. Telegram allows about
+/// 20 messages per minute in groups.
+pub const DEFAULT_QUOTA: Quota = Quota::per_minute(20);
+
+/// Maximum length of a message text in UTF-16 code units, after the
+/// formatting has been parsed
+const MESSAGE_LIMIT: usize = 4096;
+
+/// Titles are cut off earlier, so that there is room for the description
+const TITLE_LIMIT: usize = 256;
+
+const LINK_TEXT: &str = "Open";
+
+/// Either the numeric ID of a chat or the username of a channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChatId {
+    Id(i64),
+    Username(String),
+}
+
+impl fmt::Display for ChatId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatId::Id(v) => write!(f, "{v}"),
+            ChatId::Username(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseMode {
+    #[default]
+    Html,
+    MarkdownV2,
+}
+
+impl ParseMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ParseMode::Html => "HTML",
+            ParseMode::MarkdownV2 => "MarkdownV2",
+        }
+    }
+
+    /// Escapes text, so that it is displayed as is
+    fn escape(&self, s: &str) -> String {
+        match self {
+            ParseMode::Html => escape_html(s),
+            ParseMode::MarkdownV2 => escape_markdown(s, |c| {
+                matches!(
+                    c,
+                    '_' | '*'
+                        | '['
+                        | ']'
+                        | '('
+                        | ')'
+                        | '~'
+                        | '`'
+                        | '>'
+                        | '#'
+                        | '+'
+                        | '-'
+                        | '='
+                        | '|'
+                        | '{'
+                        | '}'
+                        | '.'
+                        | '!'
+                        | '\\'
+                )
+            }),
+        }
+    }
+
+    fn bold(&self, s: &str) -> String {
+        match self {
+            ParseMode::Html => format!("<b>{}</b>", self.escape(s)),
+            ParseMode::MarkdownV2 => format!("*{}*", self.escape(s)),
+        }
+    }
+
+    fn link(&self, text: &str, url: &str) -> String {
+        match self {
+            ParseMode::Html => {
+                format!("<a href=\"{}\">{}</a>", escape_html(url), self.escape(text))
+            }
+            ParseMode::MarkdownV2 => format!(
+                "[{}]({})",
+                self.escape(text),
+                escape_markdown(url, |c| c == ')' || c == '\\')
+            ),
+        }
+    }
+}
+
+/// Templates for the parts of the message. The rendered text is escaped for
+/// the parse mode.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Templates {
+    title: Option<Template>,
+    description: Option<Template>,
+    link: Option<Template>,
+}
+
+impl Templates {
+    fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.link.is_none()
+    }
+}
+
+/// The chat and how messages are displayed in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chat {
+    pub id: ChatId,
+    pub parse_mode: ParseMode,
+    pub link_preview: bool,
+    pub template: Templates,
+}
+
+impl Chat {
+    fn message<'a, T>(&self, item: &'a T) -> Result<Message<'_>>
+    where
+        T: FeedItem<'a>,
+    {
+        let mut title = item
+            .title_as_text()
+            .map_err(FeedError::from)?
+            .map(|v| v.trim().to_string());
+        let mut description = item
+            .description_as_text()
+            .map_err(FeedError::from)?
+            .map(|v| v.trim().to_string());
+        let link = item.link();
+        let mut link_text = LINK_TEXT.to_string();
+
+        if !self.template.is_empty() {
+            let ctx = Context::try_from_item(item)?;
+
+            if let Some(t) = &self.template.title {
+                title = Some(t.render(&ctx)?.trim().to_string());
+            }
+            if let Some(t) = &self.template.description {
+                description = Some(t.render(&ctx)?.trim().to_string());
+            }
+            if let Some(t) = &self.template.link {
+                link_text = truncate(t.render(&ctx)?.trim(), TITLE_LIMIT, Length::Utf16);
+            }
+        }
+
+        let mut parts = Vec::with_capacity(3);
+        let mut remaining = MESSAGE_LIMIT;
+
+        // Separators and the link text count towards the limit
+        if let Some(url) = link {
+            remaining -= Length::Utf16.count(&link_text) + 2;
+            parts.push(self.parse_mode.link(&link_text, url));
+        }
+
+        if let Some(title) = title.filter(|v| !v.is_empty()) {
+            let title = truncate(&title, TITLE_LIMIT, Length::Utf16);
+            remaining -= Length::Utf16.count(&title) + 2;
+            parts.insert(0, self.parse_mode.bold(&title));
+        }
+
+        if let Some(description) = description.filter(|v| !v.is_empty()) {
+            let description = truncate(&description, remaining, Length::Utf16);
+            let i = parts.len() - usize::from(link.is_some());
+            parts.insert(i, self.parse_mode.escape(&description));
+        }
+
+        if parts.is_empty() {
+            return Err(FeedError::Item(
+                "item has neither title, description nor link".to_string(),
+            )
+            .into());
+        }
+
+        Ok(Message {
+            chat_id: &self.id,
+            text: parts.join("\n\n"),
+            parse_mode: self.parse_mode.as_str(),
+            link_preview_options: LinkPreviewOptions {
+                is_disabled: !self.link_preview,
+            },
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Telegram {
+    api_url: Url,
+    url: Url,
+    chat: Chat,
+    limiter: Arc<RateLimiter>,
+    client: Client,
+}
+
+impl Telegram {
+    /// Returns the URL of the `sendMessage` method
+    pub fn url(api_url: &Url, token: &str) -> Result<Url> {
+        let mut url = api_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| {
+                Error::Config(format!(
+                    "invalid Telegram API URL with scheme \"{}\"",
+                    api_url.scheme()
+                ))
+            })?
+            .pop_if_empty()
+            .push(&format!("bot{token}"))
+            .push("sendMessage");
+
+        Ok(url)
+    }
+
+    pub fn new(
+        api_url: Url,
+        token: &str,
+        chat: Chat,
+        limiter: Arc<RateLimiter>,
+        client: Client,
+    ) -> Result<Self> {
+        Ok(Self {
+            url: Self::url(&api_url, token)?,
+            api_url,
+            chat,
+            limiter,
+            client,
+        })
+    }
+
+    /// Removes the URL, which contains the bot token, from the error
+    fn redact(&self, err: Error) -> Error {
+        match err {
+            Error::Request(e) => Error::Request(e.without_url()),
+            err => err,
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for Telegram {
+    #[tracing::instrument(
+        name = "push",
+        skip(self, items),
+        fields(
+            api_url = %self.api_url,
+            chat_id = %self.chat.id,
+        )
+        level = "debug"
+    )]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        debug!(count = items.len(), "pushing items");
+
+        let messages = items
+            .iter()
+            .map(|item| skip_failed_render(self.chat.message(item)))
+            .collect::<Result<Vec<_>>>()?;
+
+        for v in messages.iter().flatten() {
+            self.limiter.acquire().await;
+            retry::send(self.client.post(self.url.as_ref()).json(v))
+                .await
+                .map_err(|e| self.redact(e))?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "shutdown",
+        skip(self),
+        fields(
+            api_url = %self.api_url,
+            chat_id = %self.chat.id,
+        )
+        level = "debug"
+    )]
+    async fn shutdown(self) -> Result<()> {
+        debug!("shutting down");
+        Ok(())
+    }
+}
+
+/// Returns the JSON request bodies that would be sent for the items
+pub fn render<'a, T>(chat: &Chat, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    items
+        .iter()
+        .filter_map(|item| skip_failed_render(chat.message(item)).transpose())
+        .map(|v| Ok(serde_json::to_string_pretty(&v?)?))
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+struct Message<'a> {
+    chat_id: &'a ChatId,
+    text: String,
+    parse_mode: &'static str,
+    link_preview_options: LinkPreviewOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct LinkPreviewOptions {
+    is_disabled: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_text() {
+        let cases = [
+            (
+                ParseMode::Html,
+                "a < b & \"c\" > d",
+                "a &lt; b &amp; &quot;c&quot; &gt; d",
+            ),
+            (ParseMode::Html, "*_[]()`", "*_[]()`"),
+            (ParseMode::MarkdownV2, "plain text", "plain text"),
+            (
+                ParseMode::MarkdownV2,
+                "_*[]()~`>#+-=|{}.!\\",
+                "\\_\\*\\[\\]\\(\\)\\~\\`\\>\\#\\+\\-\\=\\|\\{\\}\\.\\!\\\\",
+            ),
+            (ParseMode::MarkdownV2, "a < b & \"c\"", "a < b & \"c\""),
+        ];
+
+        for (mode, value, expected) in cases {
+            assert_eq!(mode.escape(value), expected, "{mode:?} {value:?}");
+        }
+    }
+
+    #[test]
+    fn format_text() {
+        let cases = [
+            (
+                ParseMode::Html,
+                ParseMode::Html.bold("a & b"),
+                "<b>a &amp; b</b>",
+            ),
+            (
+                ParseMode::MarkdownV2,
+                ParseMode::MarkdownV2.bold("a*b"),
+                "*a\\*b*",
+            ),
+            (
+                ParseMode::Html,
+                ParseMode::Html.link("<Open>", "https://example.com/?a=1&b=\"2\""),
+                "<a href=\"https://example.com/?a=1&amp;b=&quot;2&quot;\">&lt;Open&gt;</a>",
+            ),
+            // Only `)` and `\` must be escaped in URLs
+            (
+                ParseMode::MarkdownV2,
+                ParseMode::MarkdownV2.link("Open.", "https://example.com/a_(b)\\c.d"),
+                "[Open\\.](https://example.com/a_(b\\)\\\\c.d)",
+            ),
+        ];
+
+        for (mode, result, expected) in cases {
+            assert_eq!(result, expected, "{mode:?}");
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    error::Error,
+    feed::item::{FeedItem, TryFromItem},
+    retry,
+    template::{Context, Template},
+    Result,
+};
+
+use super::{
+    custom::Object,
+    invalid,
+    rate_limit::{Quota, RateLimiter},
+    skip_failed_render, InvalidOption, Sink,
+};
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Client, IntoUrl, Url,
+};
+use serde::{de::IgnoredAny, Deserialize};
+use tracing::debug;
+
+/// Rate limit of a URL, if not configured otherwise
+pub const DEFAULT_QUOTA: Quota = Quota::per_second(5);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    #[default]
+    Post,
+    Put,
+    Patch,
+}
+
+impl From<Method> for reqwest::Method {
+    fn from(value: Method) -> Self {
+        match value {
+            Method::Post => reqwest::Method::POST,
+            Method::Put => reqwest::Method::PUT,
+            Method::Patch => reqwest::Method::PATCH,
+        }
+    }
+}
+
+/// Whether a request is sent per item or per push
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+    #[default]
+    Item,
+    Batch,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", deny_unknown_fields)]
+pub enum Auth {
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+
+/// How the requests are built
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub method: Method,
+    /// Header values are templates, rendered with the first item of a request
+    pub headers: HashMap<String, Template>,
+    pub auth: Option<Auth>,
+    /// Defaults to the JSON object of the item, or an array of objects in
+    /// batches
+    pub body: Option<Template>,
+    /// Defaults to `application/json` if the body is valid JSON and
+    /// `text/plain` otherwise
+    pub content_type: Option<String>,
+    pub delivery: Delivery,
+}
+
+impl Options {
+    /// Checks that the headers render valid names and values
+    pub fn validate(&self) -> std::result::Result<(), InvalidOption> {
+        for (name, t) in self.headers.iter() {
+            t.render_sample()
+                .map_err(Error::from)
+                .and_then(|v| header(name, &v))
+                .map_err(invalid("headers"))?;
+        }
+        if let Some(v) = &self.content_type {
+            header(CONTENT_TYPE.as_str(), v).map_err(invalid("content_type"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the payloads of the requests for the items
+    fn payloads<'a, T>(&self, items: &'a [T]) -> Result<Vec<Payload>>
+    where
+        T: FeedItem<'a>,
+    {
+        let size = match self.delivery {
+            Delivery::Item => 1,
+            Delivery::Batch => items.len().max(1),
+        };
+
+        // A batch is skipped as a whole if one of its items fails to render
+        Ok(items
+            .chunks(size)
+            .map(|chunk| skip_failed_render(self.payload(chunk)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    fn payload<'a, T>(&self, items: &'a [T]) -> Result<Payload>
+    where
+        T: FeedItem<'a>,
+    {
+        let body = match &self.body {
+            // Rendered bodies of a batch are separated by line breaks, e.g.
+            // for NDJSON
+            Some(t) => items
+                .iter()
+                .map(|item| Ok(t.render(&Context::try_from_item(item)?)?))
+                .collect::<Result<Vec<_>>>()?
+                .join("\n"),
+            None => {
+                let objects = items
+                    .iter()
+                    .map(Object::try_from_item)
+                    .collect::<Result<Vec<_>>>()?;
+
+                match self.delivery {
+                    Delivery::Item => serde_json::to_string(&objects[0])?,
+                    Delivery::Batch => serde_json::to_string(&objects)?,
+                }
+            }
+        };
+
+        let content_type = match &self.content_type {
+            Some(v) => header(CONTENT_TYPE.as_str(), v)?.1,
+            None if serde_json::from_str::<IgnoredAny>(&body).is_ok() => {
+                HeaderValue::from_static("application/json")
+            }
+            None => HeaderValue::from_static("text/plain; charset=utf-8"),
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, content_type);
+
+        if let Some(first) = items.first().filter(|_| !self.headers.is_empty()) {
+            let ctx = Context::try_from_item(first)?;
+
+            for (name, t) in self.headers.iter() {
+                let (name, value) = header(name, &t.render(&ctx)?)?;
+                headers.insert(name, value);
+            }
+        }
+
+        Ok(Payload { headers, body })
+    }
+}
+
+#[derive(Debug)]
+pub struct Webhook {
+    url: Url,
+    options: Options,
+    limiter: Arc<RateLimiter>,
+    client: Client,
+}
+
+impl Webhook {
+    pub fn new<T: IntoUrl>(
+        url: T,
+        options: Options,
+        limiter: Arc<RateLimiter>,
+        client: Client,
+    ) -> Result<Self> {
+        Ok(Self {
+            url: url.into_url()?,
+            options,
+            limiter,
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for Webhook {
+    #[tracing::instrument(
+        name = "push",
+        skip(self, items),
+        fields(
+            url = %self.url,
+        )
+        level = "debug"
+    )]
+    async fn push<'a, T>(&self, items: &'a [T]) -> Result<()>
+    where
+        T: FeedItem<'a>,
+    {
+        debug!(count = items.len(), "pushing items");
+
+        for payload in self.options.payloads(items)? {
+            let req = self
+                .client
+                .request(self.options.method.into(), self.url.as_ref());
+
+            let req = match &self.options.auth {
+                Some(Auth::Basic { username, password }) => {
+                    req.basic_auth(username, Some(password))
+                }
+                Some(Auth::Bearer { token }) => req.bearer_auth(token),
+                None => req,
+            };
+
+            self.limiter.acquire().await;
+            retry::send(req.headers(payload.headers).body(payload.body)).await?;
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "shutdown",
+        skip(self),
+        fields(
+            url = %self.url,
+        )
+        level = "debug"
+    )]
+    async fn shutdown(self) -> Result<()> {
+        debug!("shutting down");
+        Ok(())
+    }
+}
+
+/// Returns the headers and bodies of the requests that would be sent for the
+/// items. The auth header is omitted.
+pub fn render<'a, T>(options: &Options, items: &'a [T]) -> Result<Vec<String>>
+where
+    T: FeedItem<'a>,
+{
+    Ok(options
+        .payloads(items)?
+        .into_iter()
+        .map(|v| {
+            let mut s = String::new();
+            for (name, value) in v.headers.iter() {
+                s.push_str(&format!(
+                    "{name}: {}\n",
+                    String::from_utf8_lossy(value.as_bytes())
+                ));
+            }
+            s.push('\n');
+            s.push_str(&v.body);
+            s
+        })
+        .collect())
+}
+
+#[derive(Debug)]
+struct Payload {
+    headers: HeaderMap,
+    body: String,
+}
+
+fn header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue)> {
+    let name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| Error::Config(format!("invalid header name \"{name}\": {e}")))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|e| Error::Config(format!("invalid value of header \"{name}\": {e}")))?;
+
+    Ok((name, value))
+}
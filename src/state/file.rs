@@ -0,0 +1,115 @@
+use crate::Result;
+
+use super::{State, Store};
+
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use tokio::fs;
+use tracing::debug;
+
+/// Stores the state of each feed as a JSON file in a directory
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    read_only: bool,
+}
+
+impl FileStore {
+    pub async fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path).await?;
+
+        Ok(Self {
+            path,
+            read_only: false,
+        })
+    }
+
+    /// Opens the store without creating the directory. Saving fails.
+    pub fn read_only<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            read_only: true,
+        }
+    }
+
+    fn file_path(&self, feed: &str) -> PathBuf {
+        self.path.join(format!("{}.json", escape_name(feed)))
+    }
+}
+
+#[async_trait]
+impl Store for FileStore {
+    #[tracing::instrument(
+        name = "load",
+        skip(self),
+        fields(
+            path = %self.path.display(),
+        )
+        level = "debug"
+    )]
+    async fn load(&self, feed: &str) -> Result<Option<State>> {
+        let data = match fs::read(self.file_path(feed)).await {
+            Ok(v) => v,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("no state found");
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let state = serde_json::from_slice(&data)?;
+
+        debug!("state loaded");
+
+        Ok(Some(state))
+    }
+
+    #[tracing::instrument(
+        name = "save",
+        skip(self, state),
+        fields(
+            path = %self.path.display(),
+        )
+        level = "debug"
+    )]
+    async fn save(&self, feed: &str, state: &State) -> Result<()> {
+        if self.read_only {
+            return Err(
+                io::Error::new(ErrorKind::PermissionDenied, "state store is read-only").into(),
+            );
+        }
+
+        let path = self.file_path(feed);
+        let tmp_path = path.with_extension("json.tmp");
+
+        let data = serde_json::to_vec_pretty(state)?;
+
+        // Write to a temporary file first, so an interrupted write never
+        // leaves a truncated state file behind.
+        fs::write(&tmp_path, data).await?;
+        fs::rename(&tmp_path, &path).await?;
+
+        debug!("state saved");
+
+        Ok(())
+    }
+}
+
+/// Escapes characters of the feed name that are not safe to use in a file name
+fn escape_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+
+    for b in name.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => escaped.push(b as char),
+            _ => escaped.push_str(&format!("%{b:02X}")),
+        }
+    }
+
+    escaped
+}
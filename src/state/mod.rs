@@ -0,0 +1,252 @@
+pub mod file;
+
+use crate::{feed::item::FeedItem, Result};
+
+use self::file::FileStore;
+
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    path::PathBuf,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Minimum number of item IDs that are remembered per sink
+const SEEN_IDS_CAPACITY: usize = 1000;
+
+/// Watcher state that is kept across restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Delivery state of each sink
+    #[serde(default)]
+    pub sinks: BTreeMap<String, Cursor>,
+    #[serde(flatten)]
+    pub validators: Validators,
+}
+
+/// Items that have already been delivered to a sink
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(default)]
+    pub last_date: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub seen: SeenIds,
+}
+
+impl Cursor {
+    pub fn is_empty(&self) -> bool {
+        self.last_date.is_none() && self.seen.is_empty()
+    }
+
+    /// Marks the items as seen and returns `true` if the cursor has changed.
+    pub fn mark_seen<'a, I>(&mut self, items: &'a [I]) -> bool
+    where
+        I: FeedItem<'a>,
+    {
+        let mut changed = false;
+
+        for item in items {
+            if let Some(date) = item.date() {
+                if self.last_date.map_or(true, |v| date > v) {
+                    debug!(%date, "updating last date");
+                    self.last_date = Some(date);
+                    changed = true;
+                }
+            }
+
+            if let Some(id) = item.id() {
+                changed |= self.seen.insert(id);
+            }
+        }
+
+        // IDs of items that are still in the feed must be kept, otherwise
+        // they would be detected as new again.
+        let capacity = SEEN_IDS_CAPACITY.max(items.len());
+        self.seen.truncate(capacity, |id| {
+            items.iter().any(|item| item.id() == Some(id))
+        });
+
+        changed
+    }
+}
+
+/// HTTP cache validators of the last fetched feed document
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+/// Bounded set of item IDs, that forgets the oldest IDs first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+pub struct SeenIds {
+    order: VecDeque<String>,
+    set: HashSet<String>,
+}
+
+impl SeenIds {
+    pub fn contains(&self, id: &str) -> bool {
+        self.set.contains(id)
+    }
+
+    /// Adds the ID to the set and returns `true` if it was not present yet.
+    pub fn insert(&mut self, id: &str) -> bool {
+        if self.set.contains(id) {
+            return false;
+        }
+
+        self.set.insert(id.to_string());
+        self.order.push_back(id.to_string());
+
+        true
+    }
+
+    /// Removes the oldest IDs until the set holds at most `capacity` IDs.
+    /// IDs for which `keep` returns `true` are not removed.
+    pub fn truncate<F>(&mut self, capacity: usize, mut keep: F)
+    where
+        F: FnMut(&str) -> bool,
+    {
+        let mut remaining = self.order.len();
+
+        while self.order.len() > capacity && remaining > 0 {
+            remaining -= 1;
+
+            let Some(id) = self.order.pop_front() else {
+                break;
+            };
+
+            if keep(&id) {
+                self.order.push_back(id);
+            } else {
+                self.set.remove(&id);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl From<Vec<String>> for SeenIds {
+    fn from(value: Vec<String>) -> Self {
+        let mut ids = Self::default();
+        for id in value.iter() {
+            ids.insert(id);
+        }
+
+        ids
+    }
+}
+
+impl From<SeenIds> for Vec<String> {
+    fn from(value: SeenIds) -> Self {
+        value.order.into()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type", deny_unknown_fields)]
+pub enum StoreOptions {
+    File { path: PathBuf },
+}
+
+impl StoreOptions {
+    pub async fn store(self) -> Result<AnyStore> {
+        let store = match self {
+            StoreOptions::File { path } => AnyStore::File(FileStore::new(path).await?),
+        };
+
+        Ok(store)
+    }
+
+    /// Returns a store that is only read from, e.g. for previews
+    pub fn read_only_store(self) -> AnyStore {
+        match self {
+            StoreOptions::File { path } => AnyStore::File(FileStore::read_only(path)),
+        }
+    }
+}
+
+#[async_trait]
+pub trait Store {
+    async fn load(&self, feed: &str) -> Result<Option<State>>;
+
+    async fn save(&self, feed: &str, state: &State) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub enum AnyStore {
+    File(FileStore),
+}
+
+#[async_trait]
+impl Store for AnyStore {
+    #[inline]
+    async fn load(&self, feed: &str) -> Result<Option<State>> {
+        match self {
+            AnyStore::File(s) => s.load(feed).await,
+        }
+    }
+
+    #[inline]
+    async fn save(&self, feed: &str, state: &State) -> Result<()> {
+        match self {
+            AnyStore::File(s) => s.save(feed, state).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_seen_ids() {
+        // IDs are separated by spaces, from the oldest to the newest
+        let cases = [
+            // Below the capacity nothing is removed
+            ("a b", 2, "", "a b"),
+            ("", 0, "", ""),
+            // The oldest IDs are removed first
+            ("a b c d", 2, "", "c d"),
+            ("a b c", 0, "", ""),
+            // Kept IDs are moved behind the newest ones
+            ("a b c d", 2, "a", "d a"),
+            ("a b c d", 2, "b c", "b c"),
+            ("a b c d", 3, "b c", "b c d"),
+            // Kept IDs may exceed the capacity
+            ("a b c d", 1, "a b c", "a b c"),
+        ];
+
+        for (ids, capacity, keep, expected) in cases {
+            let ids = ids.split_whitespace().collect::<Vec<_>>();
+            let keep = keep.split_whitespace().collect::<Vec<_>>();
+            let expected = expected.split_whitespace().collect::<Vec<_>>();
+
+            let mut seen = SeenIds::from(ids.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+            seen.truncate(capacity, |id| keep.contains(&id));
+
+            assert_eq!(Vec::<String>::from(seen.clone()), expected, "{ids:?}");
+            for id in ids.iter() {
+                assert_eq!(seen.contains(id), expected.contains(id), "{ids:?} {id}");
+            }
+        }
+    }
+
+    #[test]
+    fn insert_seen_ids() {
+        let mut seen = SeenIds::from(vec!["a".to_string(), "b".to_string(), "a".to_string()]);
+
+        assert!(!seen.insert("b"));
+        assert!(seen.insert("c"));
+        assert_eq!(Vec::<String>::from(seen), ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{error::Error, sink::Sink, state::State, watcher::Watcher, Result};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::{
+    sync::{
+        broadcast::{self, Sender},
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+    },
+    task::{AbortHandle, JoinHandle},
+    time,
+};
+use tracing::{error, info};
+
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(5);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(600);
+
+/// Decides what happens when a watcher fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Stops all watchers and exits the process
+    #[default]
+    Exit,
+    /// Restarts the watcher with an exponential backoff
+    Restart,
+    /// Stops the watcher, the other watchers keep running
+    Disable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    /// The watcher failed and waits for its restart
+    Restarting {
+        failures: u32,
+        error: String,
+    },
+    /// The watcher failed and was stopped
+    Disabled {
+        error: String,
+    },
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Running => write!(f, "running"),
+            Status::Restarting { failures, error } => {
+                write!(f, "restarting after {failures} failure(s): {error}")
+            }
+            Status::Disabled { error } => write!(f, "disabled: {error}"),
+        }
+    }
+}
+
+/// The status of every supervised feed
+#[derive(Debug, Clone, Default)]
+pub struct Health(Arc<Mutex<BTreeMap<String, Status>>>);
+
+impl Health {
+    fn set(&self, feed: &str, status: Status) {
+        self.0.lock().unwrap().insert(feed.to_string(), status);
+    }
+
+    fn remove(&self, feed: &str) {
+        self.0.lock().unwrap().remove(feed);
+    }
+
+    /// Returns the feeds that are not running
+    pub fn degraded(&self) -> Vec<(String, Status)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, status)| **status != Status::Running)
+            .map(|(feed, status)| (feed.clone(), status.clone()))
+            .collect()
+    }
+}
+
+/// Runs the watchers and handles their failures according to their policy
+#[derive(Debug)]
+pub struct Supervisor {
+    watchers: HashMap<String, Handle>,
+    health: Health,
+    failures_tx: UnboundedSender<Error>,
+    failures_rx: UnboundedReceiver<Error>,
+}
+
+#[derive(Debug)]
+struct Handle {
+    stop: Sender<()>,
+    /// Returns the state of the watcher, unless it failed
+    task: JoinHandle<Option<State>>,
+    abort: AbortHandle,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        let (failures_tx, failures_rx) = mpsc::unbounded_channel();
+
+        Self {
+            watchers: HashMap::new(),
+            health: Health::default(),
+            failures_tx,
+            failures_rx,
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        self.health.clone()
+    }
+
+    /// Starts the watcher.
+    ///
+    /// With the [`FailurePolicy::Restart`] policy, `rebuild` creates the
+    /// watcher and its sinks again on each restart, so that broken sinks
+    /// are replaced. A running watcher of the same feed must be stopped
+    /// first.
+    pub fn spawn<T, F>(&mut self, mut watcher: Watcher<T>, policy: FailurePolicy, rebuild: F)
+    where
+        T: Sink + Send + Sync + 'static,
+        F: Fn() -> Result<Watcher<T>> + Send + 'static,
+    {
+        let name = watcher.name().to_string();
+        let (stop, mut kill) = broadcast::channel(1);
+        let health = self.health.clone();
+        let failures = self.failures_tx.clone();
+
+        let inner = tokio::spawn(async move {
+            let name = watcher.name().to_string();
+            let mut failures = 0;
+
+            info!("starting watcher for \"{name}\"");
+            health.set(&name, Status::Running);
+
+            loop {
+                let started = Instant::now();
+
+                let Err(err) = watcher.watch(&mut kill).await else {
+                    break;
+                };
+
+                match policy {
+                    FailurePolicy::Exit => {
+                        error!(
+                            feed = %name,
+                            error = %err,
+                            "shutting down watcher due to an error",
+                        );
+                        return Err(err);
+                    }
+                    FailurePolicy::Disable => {
+                        error!(
+                            feed = %name,
+                            error = %err,
+                            "disabling watcher due to an error",
+                        );
+                        health.set(
+                            &name,
+                            Status::Disabled {
+                                error: err.to_string(),
+                            },
+                        );
+
+                        // The other watchers keep running regardless
+                        let state = watcher.state();
+                        if let Err(err) = watcher.shutdown().await {
+                            error!(feed = %name, error = %err, "error while shutting down watcher");
+                        }
+                        return Ok(state);
+                    }
+                    FailurePolicy::Restart => {
+                        // A watcher that ran for a while is considered healthy again
+                        if started.elapsed() > MAX_RESTART_DELAY {
+                            failures = 0;
+                        }
+                        failures += 1;
+
+                        let delay = restart_delay(failures);
+
+                        error!(
+                            feed = %name,
+                            error = %err,
+                            failures,
+                            ?delay,
+                            "restarting watcher due to an error",
+                        );
+                        health.set(
+                            &name,
+                            Status::Restarting {
+                                failures,
+                                error: err.to_string(),
+                            },
+                        );
+
+                        tokio::select! {
+                            biased;
+                            _ = kill.recv() => break,
+                            _ = time::sleep(delay) => {},
+                        }
+
+                        info!("restarting watcher for \"{name}\"");
+                        match rebuild() {
+                            Ok(next) => watcher = watcher.hand_over(next).await,
+                            Err(err) => error!(
+                                feed = %name,
+                                error = %err,
+                                "error while rebuilding watcher, keeping the previous sinks",
+                            ),
+                        }
+                        health.set(&name, Status::Running);
+                    }
+                }
+            }
+
+            let state = watcher.state();
+            if let Err(err) = watcher.shutdown().await {
+                error!(feed = %name, error = %err, "error while shutting down watcher");
+            }
+
+            Ok(state)
+        });
+
+        let abort = inner.abort_handle();
+
+        // Reports the failure as soon as it happens, not when the watcher
+        // is stopped.
+        let task = tokio::spawn(async move {
+            let err = match inner.await {
+                Ok(Ok(state)) => return Some(state),
+                Ok(Err(err)) => err,
+                Err(err) if err.is_cancelled() => return None,
+                Err(err) => Error::from(err),
+            };
+
+            let _ = failures.send(err);
+
+            None
+        });
+
+        self.watchers.insert(name, Handle { stop, task, abort });
+    }
+
+    /// Stops the watcher of the feed gracefully, waits until its sinks are
+    /// shut down and returns its state, unless it failed
+    pub async fn stop(&mut self, feed: &str) -> Result<Option<State>> {
+        let Some(handle) = self.watchers.remove(feed) else {
+            return Ok(None);
+        };
+
+        // There is no receiver if the watcher has already stopped
+        let _ = handle.stop.send(());
+        let state = handle.task.await?;
+
+        self.health.remove(feed);
+
+        Ok(state)
+    }
+
+    /// Stops all watchers gracefully
+    pub async fn shutdown(&mut self) -> Result<()> {
+        let feeds: Vec<String> = self.watchers.keys().cloned().collect();
+
+        for handle in self.watchers.values() {
+            let _ = handle.stop.send(());
+        }
+
+        for feed in feeds {
+            self.stop(&feed).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Aborts all watchers without shutting down their sinks
+    pub async fn abort(&mut self) {
+        for handle in self.watchers.values() {
+            handle.abort.abort();
+        }
+
+        for (_, handle) in self.watchers.drain() {
+            let _ = handle.task.await;
+        }
+    }
+
+    /// Waits until a watcher fails with the [`FailurePolicy::Exit`] policy
+    pub async fn failed(&mut self) -> Error {
+        self.failures_rx
+            .recv()
+            .await
+            .expect("supervisor should hold a sender")
+    }
+}
+
+fn restart_delay(failures: u32) -> Duration {
+    MIN_RESTART_DELAY
+        .saturating_mul(2_u32.saturating_pow(failures.saturating_sub(1)))
+        .min(MAX_RESTART_DELAY)
+}
@@ -0,0 +1,180 @@
+//! User-defined templates for the text fields of sinks
+//!
+//! Templates use the [MiniJinja](https://docs.rs/minijinja) syntax and are
+//! rendered with a [`Context`] built from the item.
+
+use crate::{
+    error::FeedError,
+    feed::item::{Author, Enclosure, FeedItem, Source, TryFromItem},
+    sink::{self, Length},
+};
+
+use std::fmt::{self, Write};
+
+use chrono::{DateTime, FixedOffset};
+use minijinja::{Environment, ErrorKind, UndefinedBehavior};
+use serde::{Deserialize, Deserializer, Serialize};
+
+const NAME: &str = "template";
+
+const DEFAULT_DATE_FORMAT: &str = "%d %b %Y %I:%M %p %Z";
+
+/// A compiled template
+#[derive(Clone)]
+pub struct Template {
+    source: String,
+    env: Box<Environment<'static>>,
+}
+
+impl Template {
+    /// Compiles the template and renders it once with a sample item,
+    /// so that syntax errors, unknown filters and unknown variables are
+    /// reported when the config is loaded.
+    pub fn new(source: String) -> Result<Self, minijinja::Error> {
+        let mut env = Environment::new();
+        env.add_filter("dateformat", dateformat);
+        env.add_filter("truncate", truncate);
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.add_template_owned(NAME, source.clone())?;
+
+        env.get_template(NAME)?.render(Context::sample())?;
+
+        // Missing values of real items should not fail the push
+        env.set_undefined_behavior(UndefinedBehavior::Lenient);
+
+        Ok(Self {
+            source,
+            env: Box::new(env),
+        })
+    }
+
+    pub fn render(&self, ctx: &Context) -> Result<String, minijinja::Error> {
+        self.env.get_template(NAME)?.render(ctx)
+    }
+
+    /// Renders the template with the sample item, e.g. to check the result
+    pub fn render_sample(&self) -> Result<String, minijinja::Error> {
+        self.render(&Context::sample())
+    }
+}
+
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Debug for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Template").field(&self.source).finish()
+    }
+}
+
+impl<'de> Deserialize<'de> for Template {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+        Template::new(source)
+            .map_err(|e| serde::de::Error::custom(format!("invalid template: {e}")))
+    }
+}
+
+/// The variables available in templates
+#[derive(Debug, Serialize)]
+pub struct Context<'a> {
+    pub title: Option<String>,
+    /// Description as plain text
+    pub description: Option<String>,
+    /// Content as plain text
+    pub content: Option<String>,
+    pub link: Option<&'a str>,
+    pub id: Option<&'a str>,
+    pub date: Option<DateTime<FixedOffset>>,
+    pub authors: Vec<Author<'a>>,
+    pub source: Option<&'a Source<'a>>,
+    pub categories: Vec<&'a str>,
+    pub enclosures: Vec<Enclosure<'a>>,
+}
+
+impl Context<'static> {
+    fn sample() -> Self {
+        const SOURCE: Source<'static> = Source {
+            name: "feed",
+            title: "Feed",
+            url: Some("https://example.com"),
+        };
+
+        Self {
+            title: Some("Title".to_string()),
+            description: Some("Description".to_string()),
+            content: Some("Content".to_string()),
+            link: Some("https://example.com/item"),
+            id: Some("https://example.com/item"),
+            date: DateTime::parse_from_rfc3339("2024-01-01T12:00:00+00:00").ok(),
+            authors: vec![Author {
+                name: "Author",
+                email: Some("author@example.com"),
+                uri: Some("https://example.com/author"),
+            }],
+            source: Some(&SOURCE),
+            categories: vec!["Category"],
+            enclosures: vec![Enclosure {
+                url: "https://example.com/item.mp3",
+                mime_type: Some("audio/mpeg"),
+                length: Some(1024),
+            }],
+        }
+    }
+}
+
+impl<'a, T> TryFromItem<'a, T> for Context<'a>
+where
+    T: FeedItem<'a>,
+{
+    type Error = FeedError;
+
+    fn try_from_item(value: &'a T) -> std::result::Result<Self, Self::Error> {
+        Ok(Self {
+            title: value.title_as_text()?.map(trim),
+            description: value.description_as_text()?.map(trim),
+            content: value.content_as_text()?.map(trim),
+            link: value.link(),
+            id: value.id(),
+            date: value.date(),
+            authors: value.authors(),
+            source: value.source(),
+            categories: value.categories(),
+            enclosures: value.enclosures(),
+        })
+    }
+}
+
+/// The HTML to text conversion leaves trailing line breaks
+fn trim(s: String) -> String {
+    s.trim_end().to_string()
+}
+
+/// Formats a date with a [chrono format string](https://docs.rs/chrono/latest/chrono/format/strftime/index.html).
+/// Undated items result in an empty string.
+fn dateformat(value: Option<&str>, format: Option<&str>) -> Result<String, minijinja::Error> {
+    let Some(value) = value else {
+        return Ok(String::new());
+    };
+
+    let date = DateTime::parse_from_rfc3339(value).map_err(|e| {
+        minijinja::Error::new(ErrorKind::InvalidOperation, "not a date").with_source(e)
+    })?;
+
+    let mut s = String::new();
+    write!(s, "{}", date.format(format.unwrap_or(DEFAULT_DATE_FORMAT)))
+        .map_err(|_| minijinja::Error::new(ErrorKind::InvalidOperation, "invalid date format"))?;
+
+    Ok(s)
+}
+
+/// Shortens a string to at most `length` characters
+fn truncate(value: &str, length: usize) -> String {
+    sink::truncate(value, length, Length::Chars)
+}
@@ -1,187 +1,547 @@
 use crate::{
-    error::Error,
-    feed::{item::FeedItem, Feed},
+    feed::{
+        item::{FeedItem, Item},
+        Feed,
+    },
+    filter::Filter,
+    retry::{self, is_retriable, RetryPolicy},
     sink::Sink,
+    state::{AnyStore, Cursor, State, Store, Validators},
     Result,
 };
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use chrono::{DateTime, FixedOffset};
-use reqwest::{Client, IntoUrl, Url};
-use tokio::sync::broadcast::Receiver;
-use tracing::{debug, error};
+use chrono::{DateTime, Utc};
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, IntoUrl, StatusCode, Url,
+};
+use serde::Deserialize;
+use tokio::{
+    sync::broadcast::Receiver,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{debug, error, info};
 
 const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
 
+/// Decides which items are considered new
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Detection {
+    /// Items newer than the last seen item
+    #[default]
+    #[serde(rename = "date")]
+    Date,
+    /// Items with an ID that has not been seen yet
+    #[serde(rename = "guid")]
+    Guid,
+    /// Items with an ID that has not been seen yet and which are not older
+    /// than the last seen item
+    #[serde(rename = "guid+date")]
+    GuidDate,
+}
+
+/// Decides how items without a valid date are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Undated {
+    /// Items are never forwarded
+    Skip,
+    /// Items with an unseen ID are forwarded with the fetch time as date
+    FetchTime,
+    /// Items with an unseen ID are forwarded without date
+    #[default]
+    Guid,
+}
+
+/// A sink and the items that have already been delivered to it
+#[derive(Debug)]
+struct Target<T: Sink> {
+    id: String,
+    sink: T,
+    cursor: Cursor,
+}
+
 #[derive(Debug)]
 pub struct Watcher<T: Sink> {
+    name: String,
     url: Url,
-    sink: T,
+    targets: Vec<Target<T>>,
     interval: Duration,
     client: Client,
     retry_limit: usize,
-    retries_left: usize,
-    last_date: DateTime<FixedOffset>,
+    retry: RetryPolicy,
+    validators: Validators,
+    detection: Detection,
+    undated: Undated,
+    filter: Option<Filter>,
+    headers: HeaderMap,
+    store: Option<Arc<AnyStore>>,
 }
 
 impl<T: Sink> Watcher<T> {
+    /// Creates a new watcher that pushes new items to every sink.
+    ///
+    /// Each sink is identified by an ID, under which its delivery state is
+    /// stored.
     pub fn new<U: IntoUrl>(
+        name: String,
         url: U,
-        sink: T,
+        sinks: Vec<(String, T)>,
         interval: Option<Duration>,
         client: Client,
         retry_limit: usize,
     ) -> Result<Self> {
+        let targets = sinks
+            .into_iter()
+            .map(|(id, sink)| Target {
+                id,
+                sink,
+                cursor: Cursor::default(),
+            })
+            .collect();
+
         Ok(Self {
+            name,
             url: url.into_url()?,
-            sink,
+            targets,
             interval: interval.unwrap_or(DEFAULT_INTERVAL),
             client,
             retry_limit,
-            retries_left: retry_limit,
-            last_date: DateTime::default(),
+            retry: RetryPolicy::default(),
+            validators: Validators::default(),
+            detection: Detection::default(),
+            undated: Undated::default(),
+            filter: None,
+            headers: HeaderMap::new(),
+            store: None,
         })
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn with_detection(mut self, detection: Detection) -> Self {
+        self.detection = detection;
+        self
+    }
+
+    pub fn with_undated(mut self, undated: Undated) -> Self {
+        self.undated = undated;
+        self
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Adds headers to the feed requests
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Persists the watcher state in the given store and restores the
+    /// previously saved state.
+    ///
+    /// If `backfill` is `true`, items published since the restored state
+    /// will be forwarded on the first check. Otherwise they are skipped.
+    pub async fn with_store(mut self, store: Arc<AnyStore>, backfill: bool) -> Result<Self> {
+        match store.load(&self.name).await? {
+            Some(mut state) if backfill => {
+                for target in self.targets.iter_mut() {
+                    if let Some(cursor) = state.sinks.remove(&target.id) {
+                        info!(
+                            feed = %self.name,
+                            sink = %target.id,
+                            since = ?cursor.last_date,
+                            "restored state, missed items will be forwarded",
+                        );
+                        target.cursor = cursor;
+                    }
+                }
+                self.validators = state.validators;
+            }
+            Some(_) => debug!(feed = %self.name, "backfill disabled, ignoring state"),
+            None => {}
+        }
+
+        self.store = Some(store);
+
+        Ok(self)
+    }
+
     #[tracing::instrument(
         name = "watch",
         skip(self, kill),
         fields(
+            feed = %self.name,
             url = %self.url,
             interval = ?self.interval,
-            retry_limit = self.retry_limit,
+            detection = ?self.detection,
+            undated = ?self.undated,
+            max_attempts = self.max_attempts(),
         )
         level = "debug"
     )]
-    pub async fn watch(mut self, mut kill: Receiver<()>) -> Result<()> {
-        let mut interval = tokio::time::interval(self.interval);
+    pub async fn watch(&mut self, kill: &mut Receiver<()>) -> Result<()> {
+        let mut interval = time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut retries = 0;
+        let mut backoff = None;
 
         loop {
-            tokio::select! {
-                biased;
-                _ = kill.recv() => break,
-                _ = interval.tick() => {},
+            // Retries are scheduled independently of the interval
+            match backoff.take() {
+                Some(delay) => tokio::select! {
+                    biased;
+                    _ = kill.recv() => break,
+                    _ = time::sleep(delay) => {},
+                },
+                None => tokio::select! {
+                    biased;
+                    _ = kill.recv() => break,
+                    _ = interval.tick() => {},
+                },
             };
 
-            let feed = match self.fetch().await {
-                Ok(c) => c,
-                Err(err) => {
-                    if is_retriable(&err) && self.retries_left > 0 {
-                        self.retries_left -= 1;
-                        error!(
-                            error = %err,
-                            retries_left = self.retries_left,
-                            "error while fetching feed",
-                        );
-                        continue;
-                    } else {
-                        return Err(err);
+            match self.check().await {
+                Ok(()) => {
+                    if retries != 0 {
+                        debug!("resetting retries");
+                        retries = 0;
                     }
                 }
-            };
+                Err(err) if is_retriable(&err) && retries + 1 < self.max_attempts() => {
+                    retries += 1;
+                    // Rate limited requests must not be retried earlier
+                    let delay = self
+                        .retry
+                        .delay(retries)
+                        .max(retry::retry_after(&err).unwrap_or_default());
+                    error!(
+                        error = %err,
+                        retries,
+                        ?delay,
+                        "error while checking feed, retrying",
+                    );
+                    backoff = Some(delay);
+                }
+                Err(err) => return Err(err),
+            }
+        }
 
-            let items = feed.items();
+        Ok(())
+    }
 
-            let Some(last) = items.first() else {
-                debug!("no items in feed");
-                continue;
-            };
+    fn max_attempts(&self) -> usize {
+        self.retry
+            .max_attempts
+            .unwrap_or(self.retry_limit.saturating_add(1))
+    }
 
-            if self.last_date.timestamp() == 0 {
-                debug!(
-                    date = %last.date(),
-                    "no date set, setting to last item date",
-                );
-                self.last_date = last.date();
-                continue;
+    /// Fetches the feed and pushes the new items to the sinks.
+    ///
+    /// If a sink fails, the other sinks are still handled and their state is
+    /// saved. The first non-retriable error, or else the first error, is
+    /// returned afterwards.
+    async fn check(&mut self) -> Result<()> {
+        let (feed, validators) = match self.fetch().await? {
+            Some(v) => v,
+            None => {
+                debug!("feed not modified");
+                return Ok(());
             }
+        };
 
-            let Some(news) = self.get_new_items(&items) else {
-                debug!(
-                    since = %self.last_date,
-                    "found no new items",
-                );
-                continue;
-            };
+        let name = self.name.clone();
+        let items = feed.items(&name);
 
-            debug!(
-                count = news.len(),
-                since = %self.last_date,
-                "found new items",
-            );
+        if items.is_empty() {
+            debug!("no items in feed");
+            if validators != self.validators {
+                self.validators = validators;
+                self.save_state().await;
+            }
+            return Ok(());
+        }
+
+        let mut changed = false;
 
-            if let Err(err) = self.sink.push(news).await {
-                if is_retriable(&err) && self.retries_left > 0 {
-                    self.retries_left -= 1;
+        let mut failure = None;
+
+        // Each sink is handled separately, so that a failing sink does
+        // not cause duplicate deliveries on the others.
+        for i in 0..self.targets.len() {
+            match self.push_new_items(i, &items).await {
+                Ok(v) => changed |= v,
+                Err(err) => {
                     error!(
+                        sink = %self.targets[i].id,
                         error = %err,
-                        retries_left = self.retries_left,
                         "error while pushing items to sink",
                     );
-                    continue;
-                } else {
-                    return Err(err);
+                    if failure
+                        .as_ref()
+                        .map_or(true, |v| is_retriable(v) && !is_retriable(&err))
+                    {
+                        failure = Some(err);
+                    }
                 }
             }
+        }
 
-            debug!(
-                date = %last.date(),
-                "updating last date",
-            );
-            self.last_date = last.date();
+        // The validators are only kept if all items have been delivered.
+        // Otherwise the next fetch, also by a restarted watcher, would be
+        // answered with "not modified" and the items not pushed again.
+        let validators = match failure {
+            Some(_) => Validators::default(),
+            None => validators,
+        };
+        changed |= validators != self.validators;
+        self.validators = validators;
+
+        if changed {
+            self.save_state().await;
+        }
+
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the feed once without saving the state, e.g. to preview the
+    /// items with [`Preview`](crate::sink::preview::Preview) sinks.
+    ///
+    /// Sinks without a delivery state receive all current items, instead of
+    /// marking them as seen.
+    pub async fn check_once(mut self) -> Result<()> {
+        self.store = None;
+        // The feed must be fetched, even if it has not changed
+        self.validators = Validators::default();
+
+        for target in self.targets.iter_mut() {
+            if target.cursor.is_empty() {
+                target.cursor.last_date = Some(DateTime::<Utc>::MIN_UTC.fixed_offset());
+            }
+        }
+
+        let result = self.check().await;
+        self.shutdown().await?;
+
+        result
+    }
 
-            if self.retries_left != self.retry_limit {
-                debug!("resetting retries");
-                self.retries_left = self.retry_limit;
+    /// Continues from the state of a previous watcher of the same feed.
+    ///
+    /// Sinks without a delivery state in it keep their current one.
+    pub fn with_state(mut self, mut state: State) -> Self {
+        for target in self.targets.iter_mut() {
+            if let Some(cursor) = state.sinks.remove(&target.id) {
+                target.cursor = cursor;
             }
         }
+        self.validators = state.validators;
+
+        self
+    }
+
+    /// Returns the delivery state of the sinks and the cache validators
+    pub fn state(&self) -> State {
+        State {
+            sinks: self
+                .targets
+                .iter()
+                .map(|v| (v.id.clone(), v.cursor.clone()))
+                .collect(),
+            validators: self.validators.clone(),
+        }
+    }
 
+    /// Hands the delivery state and the store over to a new watcher of the
+    /// same feed, e.g. one with new sinks, and shuts down the sinks of this
+    /// watcher.
+    pub async fn hand_over(self, next: Self) -> Self {
+        let mut next = next.with_state(self.state());
+        next.store = self.store.clone();
+
+        if let Err(err) = self.shutdown().await {
+            error!(feed = %next.name, error = %err, "error while shutting down watcher");
+        }
+
+        next
+    }
+
+    /// Shuts down the sinks of the watcher
+    #[tracing::instrument(name = "shutdown", skip(self), fields(feed = %self.name), level = "debug")]
+    pub async fn shutdown(self) -> Result<()> {
         debug!("shutting down");
 
-        self.sink.shutdown().await?;
+        for target in self.targets {
+            target.sink.shutdown().await?;
+        }
 
         Ok(())
     }
 
-    fn get_new_items<'a, I>(&self, items: &'a [I]) -> Option<&'a [I]>
+    /// Pushes the new items to the sink of the target and returns `true` if
+    /// its cursor has changed.
+    async fn push_new_items(&mut self, index: usize, items: &[Item<'_>]) -> Result<bool> {
+        let target = &self.targets[index];
+
+        if target.cursor.is_empty() {
+            debug!(sink = %target.id, "no state set, marking current items as seen");
+            return Ok(self.targets[index].cursor.mark_seen(items));
+        }
+
+        let mut news = self.get_new_items(&target.cursor, items);
+
+        if news.is_empty() {
+            debug!(
+                sink = %target.id,
+                since = ?target.cursor.last_date,
+                "found no new items",
+            );
+            return Ok(self.targets[index].cursor.mark_seen(items));
+        }
+
+        debug!(
+            sink = %target.id,
+            count = news.len(),
+            since = ?target.cursor.last_date,
+            "found new items",
+        );
+
+        if let Some(filter) = &self.filter {
+            news.retain(|item| match filter.check(item) {
+                Some(rule) => {
+                    debug!(
+                        sink = %target.id,
+                        id = item.id(),
+                        title = item.title(),
+                        %rule,
+                        "item filtered",
+                    );
+                    false
+                }
+                None => true,
+            });
+
+            if news.is_empty() {
+                return Ok(self.targets[index].cursor.mark_seen(items));
+            }
+        }
+
+        if self.undated == Undated::FetchTime {
+            let now = Utc::now().fixed_offset();
+            news = news
+                .into_iter()
+                .map(|v| v.with_fallback_date(now))
+                .collect();
+        }
+
+        target.sink.push(&news).await?;
+
+        self.targets[index].cursor.mark_seen(items);
+
+        Ok(true)
+    }
+
+    fn get_new_items<'a, I>(&self, cursor: &Cursor, items: &'a [I]) -> Vec<I>
+    where
+        I: FeedItem<'a> + Copy,
+    {
+        items
+            .iter()
+            .filter(|item| self.is_new(cursor, *item))
+            .copied()
+            .collect()
+    }
+
+    fn is_new<'a, I>(&self, cursor: &Cursor, item: &'a I) -> bool
     where
         I: FeedItem<'a>,
     {
-        let mut idx = 0;
-        for (i, item) in items.iter().enumerate() {
-            if item.date().gt(&self.last_date) {
-                idx = i;
-            } else {
-                if i == 0 {
-                    return None;
+        let Some(date) = item.date() else {
+            return match item.id() {
+                Some(id) if cursor.seen.contains(id) => false,
+                Some(_) if self.undated != Undated::Skip => true,
+                id => {
+                    debug!(id, "skipping item without valid date");
+                    false
                 }
-                break;
-            }
+            };
+        };
+
+        let last_date = cursor.last_date.unwrap_or_default();
+
+        match (self.detection, item.id()) {
+            (Detection::Date, _) | (_, None) => date > last_date,
+            (Detection::Guid, Some(id)) => !cursor.seen.contains(id),
+            (Detection::GuidDate, Some(id)) => date >= last_date && !cursor.seen.contains(id),
         }
+    }
+
+    async fn save_state(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
 
-        Some(&items[..=idx])
+        if let Err(err) = store.save(&self.name, &self.state()).await {
+            error!(error = %err, "error while saving state");
+        }
     }
 
-    async fn fetch(&self) -> Result<Feed> {
+    /// Fetches the feed, unless it has not been modified since the last fetch.
+    async fn fetch(&self) -> Result<Option<(Feed, Validators)>> {
         debug!("fetching feed");
 
-        let res = self.client.get(self.url.as_ref()).send().await?;
-        let body = res.error_for_status()?.bytes().await?;
+        let mut req = self
+            .client
+            .get(self.url.as_ref())
+            .headers(self.headers.clone());
 
-        let feed = Feed::read_from(&body[..])?;
+        if let Some(etag) = &self.validators.etag {
+            req = req.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.validators.last_modified {
+            req = req.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
 
-        Ok(feed)
-    }
-}
+        let res = retry::error_for_status(req.send().await?).await?;
 
-fn is_retriable(err: &Error) -> bool {
-    match err {
-        Error::Request(e) if e.is_timeout() || e.is_connect() => true,
-        Error::Request(e) if e.is_status() => {
-            let status = e.status().unwrap();
-            status.is_server_error()
+        if res.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None);
         }
-        _ => false,
+
+        let header_value = |name| {
+            res.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+
+        let validators = Validators {
+            etag: header_value(header::ETAG),
+            last_modified: header_value(header::LAST_MODIFIED),
+        };
+
+        let content_type = header_value(header::CONTENT_TYPE);
+
+        let body = res.bytes().await?;
+
+        let feed = Feed::read_from(&body[..], content_type.as_deref())?;
+
+        Ok(Some((feed, validators)))
     }
 }